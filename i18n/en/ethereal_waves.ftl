@@ -9,7 +9,16 @@ rename-playlist = Rename Playlist
 delete-playlist = Delete Playlist
 library = Library
 update-library = Update Library
+partial-update-library = Update Library (New And Changed Only)
 updating-library = Updating Library
+analyzing-loudness = Analyzing Loudness
+analyze-loudness = Analyze Loudness
+analyze-album-loudness = Analyze Album Loudness
+write-loudness-tags-menu = Write Loudness Tags...
+write-loudness-tags = Write Loudness Tags
+write-loudness-tags-to = Write ReplayGain tags to
+files = files
+write-loudness-tags-warning = This modifies the audio files.
 scanning-paths = Scanning paths...
 loading = Loading...
 untitled-playlist = Untitled Playlist
@@ -41,8 +50,34 @@ repeat = Repeat
 playback = Playback
 repeat-one = Repeat One
 repeat-all = Repeat All
+play-next = Play Next
+add-to-queue = Add to Queue
+queue = Queue
+queue-menu = Queue...
+stop-after-current = Stop After Current Track
+sleep-timer = Sleep Timer
+sleep-timer-off = Off
+sleep-timer-minutes = { $minutes } minutes
+end-of-track = End of Track
+end-of-album = End of Album
+sleep-fade-out = Fade Out
+ab-loop = A-B Loop
+set-loop-start = Set Loop Start (A)
+set-loop-end = Set Loop End (B)
+clear-loop = Clear Loop
+queue-empty = The queue is empty
+clear-queue = Clear Queue
 add-now-playing-to = Add Now Playing to
 select-all = Select All
+locate-missing-tracks = Locate Missing Tracks
+remove-missing-tracks-menu = Remove Missing Tracks...
+remove-missing-tracks = Remove Missing Tracks
+missing-tracks-from = Missing Tracks From
+missing-file = File is missing
+import-playlist-menu = Import Playlist...
+new-smart-playlist-menu = New Smart Playlist...
+edit-smart-playlist-menu = Edit Smart Playlist...
+export-playlist-menu = Export Playlist...
 
 previous = Previous
 play = Play
@@ -58,6 +93,40 @@ delete = Delete
 delete-warning = This action cannot be undone
 by = by
 from = from
+ok = OK
+export = Export
+export-playlist = Export Playlist
+import-playlist = Import Playlist
+imported-playlist = Imported Playlist
+relative-paths = Use Relative Paths
+relative-paths-description = Write paths relative to the playlist file's folder
+new-smart-playlist = New Smart Playlist
+edit-smart-playlist = Edit Smart Playlist
+save = Save
+match = Match
+match-all = All of the following
+match-any = Any of the following
+add-rule = Add Rule
+add-group = Add Group
+limit = Limit
+no-limit = None
+sort-by = Sort by
+ascending = Ascending
+descending = Descending
+random = Random
+date-added = Date Added
+duration-minutes = Duration (minutes)
+date-added-days = Date Added (days ago)
+file-path = File Path
+rule-is = is
+rule-is-not = is not
+rule-contains = contains
+rule-does-not-contain = does not contain
+rule-greater-than = is greater than
+rule-less-than = is less than
+rule-in-last = is in the last
+rule-not-in-last = is not in the last
+unresolved-entries-in = entries could not be found in the library and are marked as missing in
 
 # Settings
 match-desktop = Match Desktop
@@ -70,6 +139,47 @@ appearance = Appearance
 theme = Theme
 add-location = Add Location
 align-rows-top = Align Rows To The Top
+watch-library = Watch Library For Changes
+scan-workers = Metadata Scan Workers
+output-device = Output Device
+default-output = Default
+resume-session = Resume Last Session
+resume-session-description = Restore the playing track and position, paused, on startup
+replay-gain = ReplayGain
+replay-gain-preamp = ReplayGain Pre-amp
+replay-gain-preamp-description = Applied to tracks with ReplayGain tags
+replay-gain-prevent-clipping = Prevent Clipping
+crossfade = Crossfade
+crossfade-description = Fade between tracks, except consecutive tracks of the same album
+playback-speed = Playback Speed
+fast-seek = Fast Seeking
+fast-seek-description = Jump to the nearest keyframe, quicker but less precise on some files
+playback-error-skipped = Couldn't play { $file }, skipped to the next track
+playback-error-stopped = Couldn't play { $file }, playback stopped
+playback-elements-missing = Some playback features are off, these GStreamer elements are missing: { $elements }
+off = Off
+track = Track
+automatic = Automatic
+
+# Equalizer
+equalizer = Equalizer
+equalizer-menu = Equalizer...
+equalizer-enabled = Enable Equalizer
+preset = Preset
+preset-name = Preset name
+save-preset = Save Preset...
+save-equalizer-preset = Save Equalizer Preset
+delete-preset = Delete Preset
+eq-flat = Flat
+eq-rock = Rock
+eq-pop = Pop
+eq-jazz = Jazz
+eq-classical = Classical
+eq-electronic = Electronic
+eq-bass-boost = Bass Boost
+eq-treble-boost = Treble Boost
+eq-vocal = Vocal
+eq-loudness = Loudness
 
 number = #
 title = Title
@@ -2,22 +2,34 @@
 
 use crate::config::{AppTheme, CONFIG_VERSION, Config, State};
 use crate::constants::*;
+use crate::equalizer::{self, EqualizerPreset};
 use crate::fl;
 use crate::footer::footer;
 use crate::helpers::*;
 use crate::image_store::ImageStore;
 use crate::key_bind::key_binds;
-use crate::library::Library;
+use crate::library::{Library, MediaMetaData};
 use crate::menu::menu_bar;
-use crate::mpris::{MediaPlayer2, MediaPlayer2Player, MprisCommand, MprisState};
+use crate::mpris::{
+    self, Capabilities, MediaPlayer2, MediaPlayer2Player, MediaPlayer2Playlists,
+    MediaPlayer2TrackList, MprisCommand, MprisPlaylist, MprisState, TrackListChange,
+};
 use crate::page::empty_library;
 use crate::page::list_view;
 use crate::page::loading;
-use crate::playback_state::{PlaybackStatus, RepeatMode};
+use crate::playback_state::{PlaybackStatus, RepeatMode, ReplayGainMode, SleepTimer};
 use crate::playlist::{Playlist, Track};
-use crate::services::library_service::{LibraryProgress, LibraryService};
+use crate::remote::{self, Remote};
+use crate::services::library_service::{LibraryChanges, LibraryProgress, LibraryService, ScanMode};
+use crate::services::library_watcher::LibraryWatcher;
+use crate::services::loudness_service::{Loudness, LoudnessProgress, LoudnessService};
 use crate::services::playback_service::{PlaybackEvent, PlaybackService};
+use crate::services::playlist_codec;
 use crate::services::playlist_service::PlaylistService;
+use crate::smart_playlist::{
+    RuleField, RuleOperator, SmartPlaylistEditor, SmartRules, SmartSortBy,
+};
+use crate::smart_playlist_dialog::smart_playlist_dialog;
 use cosmic::iced_widget::scrollable::{self, AbsoluteOffset};
 use cosmic::prelude::*;
 use cosmic::{
@@ -31,7 +43,9 @@ use cosmic::{
         alignment::{Horizontal, Vertical},
         event::{self, Event},
         font::{Font, Weight},
+        futures::SinkExt,
         keyboard::{Event as KeyEvent, Key, Modifiers, key::Named},
+        stream,
         window::Event as WindowEvent,
     },
     iced_core::text::Wrapping,
@@ -83,6 +97,26 @@ pub struct AppModel {
     pub config: Config,
     /// Settings page / app theme dropdown labels
     app_theme_labels: Vec<String>,
+    /// Settings page / scan worker dropdown labels
+    scan_worker_labels: Vec<String>,
+    /// Settings page / ReplayGain dropdown labels
+    replay_gain_labels: Vec<String>,
+    replay_gain_preamp_labels: Vec<String>,
+    /// Settings page / crossfade dropdown labels
+    crossfade_labels: Vec<String>,
+    /// Settings page / output device dropdown labels, the default output first
+    output_device_labels: Vec<String>,
+    /// Footer / playback speed dropdown labels
+    pub playback_rate_labels: Vec<String>,
+    /// Built-in followed by user equalizer presets
+    equalizer_presets: Vec<EqualizerPreset>,
+    equalizer_preset_labels: Vec<String>,
+    /// Smart playlist editor dropdown labels
+    pub smart_field_labels: Vec<String>,
+    pub smart_operator_labels: Vec<String>,
+    pub smart_match_labels: Vec<String>,
+    pub smart_sort_labels: Vec<String>,
+    pub sort_direction_labels: Vec<String>,
     pub is_condensed: bool,
 
     config_handler: Option<cosmic_config::Config>,
@@ -93,11 +127,16 @@ pub struct AppModel {
 
     pub library_service: LibraryService,
     library_update_cancel: Option<CancellationToken>,
+    pending_library_changes: LibraryChanges,
+    /// Watcher changes the running update is applying, kept in case it is cancelled
+    applying_library_changes: Option<LibraryChanges>,
     pub playback_service: PlaybackService,
 
     pub library: Library,
 
     pub is_updating: bool,
+    /// The running library job is a loudness analysis rather than a scan
+    pub is_analyzing: bool,
     pub update_progress: f32,
     pub update_total: f32,
     pub update_percent: f32,
@@ -106,6 +145,8 @@ pub struct AppModel {
     initial_load_complete: bool,
 
     dialog_pages: DialogPages,
+    /// Non-blocking notices, such as tracks that failed to play
+    toasts: widget::Toasts<Message>,
 
     view_mode: ViewMode,
 
@@ -115,6 +156,7 @@ pub struct AppModel {
     pub list_visible_row_count: usize,
     list_last_clicked: Option<Instant>,
     list_last_selected_id: Option<usize>,
+    session_saved_at: Instant,
 
     control_pressed: u8,
     shift_pressed: u8,
@@ -138,51 +180,99 @@ pub enum Message {
     AddLibraryDialog,
     AddSelectedToPlaylist(PlaylistId),
     AddNowPlayingToPlaylist(PlaylistId),
+    AddToQueue,
+    AnalyzeAlbumLoudness,
+    AnalyzeLoudness,
     AppTheme(AppTheme),
     CancelLibraryUpdate,
     ChangeTrack(String, usize),
+    ClearLoop,
+    CloseToast(widget::ToastId),
+    Crossfade(u32),
+    DeleteEqualizerPreset,
     DeletePlaylist,
     DialogCancel,
     DialogComplete,
+    EditSmartPlaylist,
+    EnqueuePaths(Vec<PathBuf>),
+    EqualizerBand(usize, i32),
+    EqualizerPreset(usize),
+    ExportPlaylist,
+    ExportPlaylistTo(PlaylistId, PathBuf, bool),
+    ImportPlaylist,
+    ImportPlaylistFrom(PathBuf),
     KeyPressed(Modifiers, Key),
     KeyReleased(Key),
     LaunchUrl(String),
+    LibraryFilesChanged(LibraryChanges),
     LibraryPathOpenError(Arc<file_chooser::Error>),
     LibraryProgress(LibraryProgress),
     ListSelectRow(usize),
     ListViewScroll(scrollable::Viewport),
     ListViewSort(SortBy),
+    LocateMissingTracks,
+    LoudnessProgress(LoudnessProgress),
+    LoudnessTagsWritten(Vec<(PathBuf, String)>),
     MoveNavDown,
     MoveNavUp,
     NewPlaylist,
+    NewSmartPlaylist,
     Next,
     Noop,
+    OpenPaths(Vec<PathBuf>),
+    OutputDevice(usize),
+    PartialUpdateLibrary,
+    PathsEnqueued(Vec<(PathBuf, MediaMetaData)>),
+    PathsOpened(Vec<(PathBuf, MediaMetaData)>),
+    PlayNext,
     PlayPause,
+    PlaylistFileError(Arc<file_chooser::Error>),
     Previous,
+    QueueClear,
+    QueueMoveDown(usize),
+    QueueMoveUp(usize),
+    QueueRemove(usize),
     Quit,
     ReleaseSlider,
     RemoveLibraryPath(String),
+    RemoveMissingTracks,
     RemoveSelectedFromPlaylist,
     RenamePlaylist,
+    ReplayGainMode(ReplayGainMode),
+    ReplayGainPreamp(i32),
+    SaveEqualizerPreset,
     SearchActivate,
     SearchClear,
     SearchInput(String),
+    ScanWorkers(usize),
     SelectAll,
     SelectedPaths(Vec<String>),
+    SetLoopEnd,
+    SetLoopStart,
+    SetPlaybackRate(f64),
     SetVolume(i32),
+    SleepTimer(Option<SleepTimer>),
     SliderSeek(f32),
     Tick,
     ToggleContextPage(ContextPage),
+    ToggleEqualizer(bool),
+    ToggleFastSeek(bool),
     ToggleListRowAlignTop(bool),
     ToggleListTextWrap(bool),
     ToggleMute,
     ToggleRepeat,
     ToggleRepeatMode,
+    ToggleReplayGainClipping(bool),
+    ToggleResumeSession(bool),
     ToggleShuffle,
+    ToggleSleepFade,
+    ToggleStopAfterCurrent,
+    ToggleWatchLibrary(bool),
     UpdateConfig(Config),
     UpdateDialog(DialogPage),
     UpdateLibrary,
     WindowResized(Size),
+    WriteLoudnessTags,
     ZoomIn,
     ZoomOut,
 }
@@ -213,10 +303,7 @@ impl cosmic::Application for AppModel {
     const APP_ID: &'static str = APP_ID;
 
     /// Initializes the application with any given flags and startup commands.
-    fn init(
-        core: cosmic::Core,
-        _flags: Self::Flags,
-    ) -> (Self, Task<cosmic::Action<Self::Message>>) {
+    fn init(core: cosmic::Core, flags: Self::Flags) -> (Self, Task<cosmic::Action<Self::Message>>) {
         // Create a nav bar with three page items.
         let nav = nav_bar::Model::default();
 
@@ -232,13 +319,27 @@ impl cosmic::Application for AppModel {
         let (mpris_tx, mpris_rx) = tokio::sync::mpsc::unbounded_channel();
         let mpris_state = Arc::new(Mutex::new(MprisState::default()));
         let mpris_state_clone = mpris_state.clone();
+        let mpris_root = MediaPlayer2 {
+            tx: mpris_tx.clone(),
+        };
+        let mpris_track_list = MediaPlayer2TrackList {
+            tx: mpris_tx.clone(),
+            state: mpris_state.clone(),
+        };
+        let mpris_playlists = MediaPlayer2Playlists {
+            tx: mpris_tx.clone(),
+            state: mpris_state.clone(),
+        };
+        let remote = Remote {
+            tx: mpris_tx.clone(),
+        };
         let (conn_tx, conn_rx) = std::sync::mpsc::sync_channel(1);
 
         tokio::spawn(async move {
             let connection = zbus::Connection::session().await.unwrap();
             connection
                 .object_server()
-                .at("/org/mpris/MediaPlayer2", MediaPlayer2)
+                .at("/org/mpris/MediaPlayer2", mpris_root)
                 .await
                 .unwrap();
             connection
@@ -253,15 +354,37 @@ impl cosmic::Application for AppModel {
                 .await
                 .unwrap();
             connection
-                .request_name("org.mpris.MediaPlayer2.ethereal-waves")
+                .object_server()
+                .at("/org/mpris/MediaPlayer2", mpris_track_list)
+                .await
+                .unwrap();
+            connection
+                .object_server()
+                .at("/org/mpris/MediaPlayer2", mpris_playlists)
+                .await
+                .unwrap();
+            connection
+                .object_server()
+                .at(remote::OBJECT_PATH, remote)
                 .await
                 .unwrap();
+            connection.request_name(remote::BUS_NAME).await.unwrap();
             let _ = conn_tx.send(connection.clone());
             futures::future::pending::<()>().await;
         });
 
         let mpris_connection = conn_rx.recv().ok();
 
+        // Nothing can play without GStreamer and playbin, everything else in the
+        // pipeline is optional
+        let playback_service = match PlaybackService::new(mpris_rx) {
+            Ok(playback_service) => playback_service,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        };
+
         let app_xdg_dirs = xdg::BaseDirectories::with_prefix("ethereal-waves");
 
         // Build out artwork cache directory
@@ -284,6 +407,37 @@ impl cosmic::Application for AppModel {
                 })
                 .unwrap_or_default(),
             app_theme_labels: vec![fl!("match-desktop"), fl!("dark"), fl!("light")],
+            scan_worker_labels: SCAN_WORKER_CHOICES
+                .iter()
+                .map(|n| match n {
+                    0 => fl!("automatic"),
+                    n => n.to_string(),
+                })
+                .collect(),
+            replay_gain_labels: vec![fl!("off"), fl!("track"), fl!("album")],
+            replay_gain_preamp_labels: REPLAY_GAIN_PREAMP_CHOICES
+                .iter()
+                .map(|db| format!("{:+} dB", db))
+                .collect(),
+            crossfade_labels: CROSSFADE_CHOICES
+                .iter()
+                .map(|secs| match secs {
+                    0 => fl!("off"),
+                    secs => format!("{} s", secs),
+                })
+                .collect(),
+            output_device_labels: Vec::new(),
+            playback_rate_labels: PLAYBACK_RATE_CHOICES
+                .iter()
+                .map(|rate| format!("{}×", rate))
+                .collect(),
+            equalizer_presets: Vec::new(),
+            equalizer_preset_labels: Vec::new(),
+            smart_field_labels: RuleField::ALL.iter().map(|f| f.label()).collect(),
+            smart_operator_labels: RuleOperator::ALL.iter().map(|o| o.label()).collect(),
+            smart_match_labels: vec![fl!("match-all"), fl!("match-any")],
+            smart_sort_labels: SmartSortBy::ALL.iter().map(|s| s.label()).collect(),
+            sort_direction_labels: vec![fl!("ascending"), fl!("descending")],
             is_condensed: false,
             config_handler: _flags.config_handler,
             state_handler: _flags.state_handler,
@@ -291,17 +445,21 @@ impl cosmic::Application for AppModel {
             app_xdg_dirs: Arc::new(app_xdg_dirs.clone()),
             library_service: LibraryService::new(Arc::new(app_xdg_dirs.clone())),
             library_update_cancel: None,
-            playback_service: PlaybackService::new(mpris_rx),
+            pending_library_changes: LibraryChanges::default(),
+            applying_library_changes: None,
+            playback_service,
             mpris_state,
             mpris_connection,
             initial_load_complete: false,
             library: Library::new(),
             is_updating: false,
+            is_analyzing: false,
             update_progress: 0.0,
             update_total: 0.0,
             update_percent: 0.0,
             update_progress_display: "0".into(),
             dialog_pages: DialogPages::new(),
+            toasts: widget::Toasts::new(Message::CloseToast),
             view_mode: ViewMode::List,
             size_multiplier: _flags.state.size_multiplier,
             list_scroll_id: widget::Id::unique(),
@@ -309,6 +467,7 @@ impl cosmic::Application for AppModel {
             list_visible_row_count: 0,
             list_last_clicked: None,
             list_last_selected_id: None,
+            session_saved_at: Instant::now(),
             control_pressed: 0,
             shift_pressed: 0,
             view_playlist: None,
@@ -318,13 +477,50 @@ impl cosmic::Application for AppModel {
             playlist_service: PlaylistService::new(Arc::new(app_xdg_dirs.clone())),
         };
 
+        app.playback_service
+            .set_replay_gain(app.config.replay_gain());
+        app.playback_service.set_crossfade(app.config.crossfade);
+        app.playback_service.set_rate(app.state.playback_rate);
+        app.playback_service
+            .set_sleep_fade(app.config.sleep_timer_fade);
+        app.playback_service.set_fast_seek(app.config.fast_seek);
+        app.playback_service
+            .set_output_device(app.config.output_device.clone());
+        app.rebuild_output_device_labels();
+        app.playback_service.set_equalizer(app.config.equalizer());
+        app.rebuild_equalizer_presets();
+
         // Create a startup command that sets the window title.
         let update_title = app.update_title();
 
         // Load the master library and playlists
         let load_data = app.load_data();
 
-        (app, Task::batch([update_title, load_data]))
+        // Play whatever was given on the command line
+        let open_paths = if flags.open_paths.is_empty() {
+            Task::none()
+        } else {
+            Task::done(cosmic::Action::App(Message::OpenPaths(flags.open_paths)))
+        };
+
+        // Playback works without the optional pipeline stages, say which are off
+        let missing_elements = app.playback_service.missing_elements().join(", ");
+        let missing_notice = if missing_elements.is_empty() {
+            Task::none()
+        } else {
+            eprintln!("Missing GStreamer elements: {}", missing_elements);
+            app.toasts
+                .push(widget::Toast::new(fl!(
+                    "playback-elements-missing",
+                    elements = missing_elements
+                )))
+                .map(cosmic::Action::App)
+        };
+
+        (
+            app,
+            Task::batch([update_title, load_data, open_paths, missing_notice]),
+        )
     }
 
     /// Elements to pack at the start of the header bar.
@@ -384,6 +580,16 @@ impl cosmic::Application for AppModel {
                 Message::ToggleContextPage(ContextPage::TrackInfo),
             )
             .title(fl!("track-info")),
+            ContextPage::Queue => context_drawer::context_drawer(
+                self.queue_panel(),
+                Message::ToggleContextPage(ContextPage::Queue),
+            )
+            .title(fl!("queue")),
+            ContextPage::Equalizer => context_drawer::context_drawer(
+                self.equalizer_panel(),
+                Message::ToggleContextPage(ContextPage::Equalizer),
+            )
+            .title(fl!("equalizer")),
         })
     }
 
@@ -404,13 +610,14 @@ impl cosmic::Application for AppModel {
             None => empty_library::content(),
         };
 
-        widget::container(widget::column().push(content))
+        let content = widget::container(widget::column().push(content))
             .apply(widget::container)
             .height(Length::Fill)
             .width(Length::Fill)
             .align_x(Horizontal::Center)
-            .align_y(Vertical::Top)
-            .into()
+            .align_y(Vertical::Top);
+
+        widget::toaster(&self.toasts, content)
     }
 
     fn dialog(&self) -> Option<Element<'_, Self::Message>> {
@@ -522,6 +729,123 @@ impl cosmic::Application for AppModel {
 
                 dialog
             }
+
+            DialogPage::RemoveMissingTracks(id) => {
+                let playlist = self.playlist_service.get(*id).ok();
+
+                widget::dialog()
+                    .title(fl!("remove-missing-tracks"))
+                    .icon(widget::icon::from_name("dialog-warning").size(64))
+                    .body(format!(
+                        "{} {} {} {}?",
+                        fl!("remove"),
+                        playlist.map(|p| p.missing_count()).unwrap_or_default(),
+                        fl!("missing-tracks-from"),
+                        playlist.map(|p| p.name()).unwrap_or_default()
+                    ))
+                    .primary_action(
+                        widget::button::suggested(fl!("yes")).on_press(Message::DialogComplete),
+                    )
+                    .secondary_action(
+                        widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                    )
+                    .control(widget::column::with_children(vec![
+                        widget::text(fl!("delete-warning")).into(),
+                    ]))
+            }
+
+            DialogPage::ExportPlaylist { id, relative } => {
+                let playlist = self.playlist_service.get(*id).ok();
+                let id = *id;
+
+                widget::dialog()
+                    .title(fl!("export-playlist"))
+                    .body(playlist.map(|p| p.name()).unwrap_or_default().to_string())
+                    .primary_action(
+                        widget::button::suggested(fl!("export")).on_press(Message::DialogComplete),
+                    )
+                    .secondary_action(
+                        widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                    )
+                    .control(widget::column::with_children(vec![
+                        settings::item::builder(fl!("relative-paths"))
+                            .description(fl!("relative-paths-description"))
+                            .control(toggler(*relative).on_toggle(move |relative| {
+                                Message::UpdateDialog(DialogPage::ExportPlaylist { id, relative })
+                            }))
+                            .into(),
+                    ]))
+            }
+
+            DialogPage::SmartPlaylist(editor) => smart_playlist_dialog(self, editor),
+
+            DialogPage::ImportReport { name, unresolved } => {
+                let entries = unresolved
+                    .iter()
+                    .map(|entry| widget::text(entry.as_str()).into())
+                    .collect::<Vec<Element<Message>>>();
+
+                widget::dialog()
+                    .title(fl!("import-playlist"))
+                    .icon(widget::icon::from_name("dialog-warning").size(64))
+                    .body(format!(
+                        "{} {} {}",
+                        unresolved.len(),
+                        fl!("unresolved-entries-in"),
+                        name
+                    ))
+                    .primary_action(
+                        widget::button::suggested(fl!("ok")).on_press(Message::DialogComplete),
+                    )
+                    .control(
+                        widget::scrollable(widget::column::with_children(entries).spacing(4))
+                            .height(Length::Fixed(200.0)),
+                    )
+            }
+
+            DialogPage::WriteLoudnessTags(paths) => widget::dialog()
+                .title(fl!("write-loudness-tags"))
+                .icon(widget::icon::from_name("dialog-warning").size(64))
+                .body(format!(
+                    "{} {} {}?",
+                    fl!("write-loudness-tags-to"),
+                    paths.len(),
+                    fl!("files")
+                ))
+                .primary_action(
+                    widget::button::suggested(fl!("yes")).on_press(Message::DialogComplete),
+                )
+                .secondary_action(
+                    widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                )
+                .control(widget::column::with_children(vec![
+                    widget::text(fl!("write-loudness-tags-warning")).into(),
+                ])),
+
+            DialogPage::SaveEqualizerPreset(name) => {
+                let complete_maybe = if name.trim().is_empty() {
+                    None
+                } else {
+                    Some(Message::DialogComplete)
+                };
+
+                widget::dialog()
+                    .title(fl!("save-equalizer-preset"))
+                    .primary_action(
+                        widget::button::suggested(fl!("save")).on_press_maybe(complete_maybe),
+                    )
+                    .secondary_action(
+                        widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel),
+                    )
+                    .control(widget::column::with_children(vec![
+                        widget::text_input(fl!("preset-name"), name)
+                            .id(widget::Id::new(EQUALIZER_PRESET_INPUT_ID))
+                            .on_input(move |name| {
+                                Message::UpdateDialog(DialogPage::SaveEqualizerPreset(name))
+                            })
+                            .into(),
+                    ]))
+            }
         };
 
         Some(dialog.into())
@@ -558,6 +882,32 @@ impl cosmic::Application for AppModel {
             }),
         ];
 
+        // Watch library paths for changes
+        if self.config.watch_library && !self.config.library_paths.is_empty() {
+            let mut roots: Vec<String> = self.config.library_paths.iter().cloned().collect();
+            roots.sort();
+
+            subscriptions.push(Subscription::run_with_id(
+                ("library-watcher", roots.clone()),
+                stream::channel(16, move |mut output| async move {
+                    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+                    LibraryWatcher::spawn(roots.into_iter().map(PathBuf::from).collect(), tx);
+
+                    while let Some(changes) = rx.recv().await {
+                        if output
+                            .send(Message::LibraryFilesChanged(changes))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+
+                    futures::future::pending::<()>().await;
+                }),
+            ));
+        }
+
         // Tick
         subscriptions.push(
             iced::time::every(Duration::from_millis(TICK_INTERVAL_MS)).map(|_| Message::Tick),
@@ -706,10 +1056,54 @@ impl cosmic::Application for AppModel {
                 }
             }
 
+            Message::AddToQueue => {
+                let tracks = self.selected_tracks();
+                self.playback_service.enqueue(tracks);
+            }
+
+            Message::AnalyzeAlbumLoudness => {
+                let groups = self.selected_album_groups();
+                return self.analyze_loudness(groups, true);
+            }
+
+            Message::AnalyzeLoudness => {
+                let groups = self
+                    .selected_tracks()
+                    .into_iter()
+                    .filter(|t| !t.missing)
+                    .map(|t| vec![t.path])
+                    .collect();
+                return self.analyze_loudness(groups, false);
+            }
+
             Message::CancelLibraryUpdate => {
                 if let Some(cancel_token) = self.library_update_cancel.take() {
                     cancel_token.cancel();
                     self.is_updating = false;
+                    self.is_analyzing = false;
+                }
+            }
+
+            Message::Crossfade(crossfade) => {
+                config_set!(crossfade, crossfade);
+                self.playback_service.set_crossfade(crossfade);
+            }
+
+            Message::ClearLoop => {
+                self.playback_service.clear_loop();
+            }
+
+            Message::CloseToast(id) => {
+                self.toasts.remove(id);
+            }
+
+            Message::DeleteEqualizerPreset => {
+                let gains = self.config.equalizer_gains;
+                let mut presets = self.config.equalizer_presets.clone();
+                if let Some(index) = presets.iter().position(|p| p.gains == gains) {
+                    presets.remove(index);
+                    config_set!(equalizer_presets, presets);
+                    self.rebuild_equalizer_presets();
                 }
             }
 
@@ -799,6 +1193,125 @@ impl cosmic::Application for AppModel {
                             }
                         }
 
+                        DialogPage::RemoveMissingTracks(id) => {
+                            match self.playlist_service.remove_missing(id) {
+                                Ok(count) => log::info!("Removed {} missing tracks", count),
+                                Err(err) => eprintln!("Error removing missing tracks: {}", err),
+                            }
+
+                            // Reset viewport scroll to top
+                            self.list_start = 0;
+                            return scrollable::scroll_to(
+                                self.list_scroll_id.clone(),
+                                AbsoluteOffset { x: 0.0, y: 0.0 },
+                            );
+                        }
+
+                        DialogPage::ExportPlaylist { id, relative } => {
+                            let file_name = match self.playlist_service.get(id) {
+                                Ok(playlist) => format!("{}.m3u8", playlist.name()),
+                                Err(_) => return Task::none(),
+                            };
+
+                            return cosmic::task::future(async move {
+                                let mut dialog = file_chooser::save::Dialog::new()
+                                    .title(fl!("export-playlist"))
+                                    .file_name(file_name);
+
+                                for codec in playlist_codec::CODECS {
+                                    let filter = codec.extensions().iter().fold(
+                                        file_chooser::FileFilter::new(codec.name()),
+                                        |filter, ext| filter.glob(format!("*.{}", ext)),
+                                    );
+                                    dialog = dialog.filter(filter);
+                                }
+
+                                match dialog.save_file().await {
+                                    Ok(response) => match response
+                                        .url()
+                                        .and_then(|url| url.to_file_path().ok())
+                                    {
+                                        Some(path) => Message::ExportPlaylistTo(id, path, relative),
+                                        None => Message::Noop,
+                                    },
+                                    Err(file_chooser::Error::Cancelled) => Message::Noop,
+                                    Err(why) => Message::PlaylistFileError(Arc::new(why)),
+                                }
+                            });
+                        }
+
+                        DialogPage::ImportReport { .. } => {}
+
+                        DialogPage::WriteLoudnessTags(paths) => {
+                            let entries: Vec<(PathBuf, Loudness)> = paths
+                                .into_iter()
+                                .filter_map(|path| {
+                                    let loudness =
+                                        self.library.media.get(&path).and_then(stored_loudness)?;
+                                    Some((path, loudness))
+                                })
+                                .collect();
+
+                            return cosmic::task::future(async move {
+                                let failed = tokio::task::spawn_blocking(move || {
+                                    LoudnessService::write_tags(&entries)
+                                })
+                                .await
+                                .unwrap_or_default();
+
+                                Message::LoudnessTagsWritten(failed)
+                            });
+                        }
+
+                        DialogPage::SaveEqualizerPreset(name) => {
+                            let name = name.trim().to_string();
+                            let gains = self.config.equalizer_gains;
+                            let mut presets = self.config.equalizer_presets.clone();
+
+                            // Saving under an existing name replaces that preset
+                            match presets.iter_mut().find(|p| p.name == name) {
+                                Some(preset) => preset.gains = gains,
+                                None => presets.push(EqualizerPreset { name, gains }),
+                            }
+
+                            config_set!(equalizer_presets, presets);
+                            self.rebuild_equalizer_presets();
+                        }
+
+                        DialogPage::SmartPlaylist(editor) => {
+                            if !editor.is_valid() {
+                                self.dialog_pages
+                                    .push_back(DialogPage::SmartPlaylist(editor));
+                                return Task::none();
+                            }
+
+                            let (id, name, rules) = editor.finish();
+
+                            let result = match id {
+                                Some(id) => self
+                                    .playlist_service
+                                    .update_smart(id, name, rules, &self.library)
+                                    .map(|_| id),
+                                None => {
+                                    self.playlist_service
+                                        .create_smart(name, rules, &self.library)
+                                }
+                            };
+
+                            match result {
+                                Ok(id) => {
+                                    self.view_playlist = Some(id);
+
+                                    // Rebuild nav preserving order
+                                    let items = self.build_ordered_nav_items();
+                                    self.rebuild_nav_from_order(items, id);
+                                }
+                                Err(err) => {
+                                    eprintln!("Error saving smart playlist: {}", err);
+                                }
+                            }
+                        }
+
                         DialogPage::DeleteSelectedFromPlaylist => {
                             let playlist_id = match self.view_playlist {
                                 Some(id) => id,
@@ -833,10 +1346,19 @@ impl cosmic::Application for AppModel {
                 }
             }
 
+            Message::LibraryFilesChanged(changes) => {
+                self.pending_library_changes.merge(changes);
+                return self.apply_pending_library_changes();
+            }
+
             Message::LibraryPathOpenError(why) => {
                 eprintln!("{why}");
             }
 
+            Message::PlaylistFileError(why) => {
+                eprintln!("{why}");
+            }
+
             Message::LibraryProgress(progress) => match progress {
                 LibraryProgress::Progress {
                     current,
@@ -863,22 +1385,121 @@ impl cosmic::Application for AppModel {
                     self.update_library_playlist();
                 }
 
+                LibraryProgress::Changes {
+                    added,
+                    updated,
+                    moved,
+                    removed,
+                } => {
+                    log::info!(
+                        "Library update: {} added, {} updated, {} moved, {} removed",
+                        added,
+                        updated,
+                        moved,
+                        removed
+                    );
+                }
+
                 LibraryProgress::Complete(library) => {
                     self.library = library;
+                    self.applying_library_changes = None;
                     if let Err(e) = self.library_service.save(&self.library) {
                         eprintln!("Error saving library: {}", e);
                     }
                     self.is_updating = false;
+
+                    // Follow moved and renamed files in user playlists
+                    match self.playlist_service.relink_tracks(&self.library) {
+                        Ok(0) => {}
+                        Ok(count) => log::info!("Relinked {} playlist tracks", count),
+                        Err(err) => eprintln!("Error relinking playlist tracks: {}", err),
+                    }
+                    self.playlist_service.validate(&self.library);
+                    self.playlist_service.refresh_smart(&self.library);
+
                     self.update_library_playlist();
+                    return self.apply_pending_library_changes();
                 }
 
                 LibraryProgress::Cancelled => {
                     self.is_updating = false;
+
+                    // Changes that weren't applied are picked up with the next update,
+                    // anything reported since is newer
+                    if let Some(mut changes) = self.applying_library_changes.take() {
+                        changes.merge(std::mem::take(&mut self.pending_library_changes));
+                        self.pending_library_changes = changes;
+                    }
+
                     self.update_library_playlist();
                     log::info!("Library update cancelled")
                 }
             },
 
+            Message::LoudnessProgress(progress) => match progress {
+                LoudnessProgress::Progress {
+                    current,
+                    total,
+                    percent,
+                } => {
+                    self.update_progress = current;
+                    self.update_total = total;
+                    self.update_percent = percent;
+                    self.update_progress_display = format!(
+                        "{} {}/{} ({:.2}%)",
+                        fl!("analyzing-loudness"),
+                        current,
+                        total,
+                        percent
+                    );
+                }
+
+                LoudnessProgress::Complete(results) => {
+                    self.is_updating = false;
+                    self.is_analyzing = false;
+
+                    let mut paths = HashSet::new();
+                    for (path, loudness) in results {
+                        if let Some(metadata) = self.library.media.get_mut(&path) {
+                            metadata.track_gain = Some(loudness.track_gain);
+                            metadata.track_peak = Some(loudness.track_peak);
+                            if loudness.album_gain.is_some() {
+                                metadata.album_gain = loudness.album_gain;
+                                metadata.album_peak = loudness.album_peak;
+                            }
+                            paths.insert(path);
+                        }
+                    }
+                    log::info!("Analyzed loudness of {} files", paths.len());
+
+                    if let Err(e) = self.library_service.save(&self.library) {
+                        eprintln!("Error saving library: {}", e);
+                    }
+                    if let Err(err) = self
+                        .playlist_service
+                        .refresh_metadata(&self.library, &paths)
+                    {
+                        eprintln!("Error updating playlist tracks: {}", err);
+                    }
+                    self.playlist_service.refresh_smart(&self.library);
+
+                    self.update_library_playlist();
+                    return self.apply_pending_library_changes();
+                }
+
+                LoudnessProgress::Cancelled => {
+                    self.is_updating = false;
+                    self.is_analyzing = false;
+                    return self.apply_pending_library_changes();
+                }
+            },
+
+            Message::LoudnessTagsWritten(failed) => {
+                for (path, err) in &failed {
+                    eprintln!("Error writing loudness tags to {:?}: {}", path, err);
+                }
+            }
+
             Message::ListSelectRow(index) => {
                 let Some(playlist_id) = self.view_playlist else {
                     return Task::none();
@@ -968,6 +1589,22 @@ impl cosmic::Application for AppModel {
                 }
             }
 
+            Message::LocateMissingTracks => {
+                if let Some(playlist_id) = self.view_playlist {
+                    match self
+                        .playlist_service
+                        .locate_missing(playlist_id, &self.library)
+                    {
+                        Ok((resolved, unresolved)) => log::info!(
+                            "Located {} missing tracks, {} could not be resolved",
+                            resolved,
+                            unresolved
+                        ),
+                        Err(err) => eprintln!("Error locating missing tracks: {}", err),
+                    }
+                }
+            }
+
             Message::LaunchUrl(url) => match open::that_detached(&url) {
                 Ok(()) => {}
                 Err(err) => {
@@ -975,6 +1612,29 @@ impl cosmic::Application for AppModel {
                 }
             },
 
+            Message::EqualizerBand(band, gain) => {
+                let mut gains = self.config.equalizer_gains;
+                if let Some(band_gain) = gains.get_mut(band) {
+                    *band_gain = gain.clamp(-EQUALIZER_MAX_GAIN, EQUALIZER_MAX_GAIN);
+                    config_set!(equalizer_gains, gains);
+                    self.playback_service.set_equalizer(self.config.equalizer());
+                }
+            }
+
+            Message::EqualizerPreset(index) => {
+                if let Some(preset) = self.equalizer_presets.get(index) {
+                    config_set!(equalizer_gains, preset.gains);
+                    config_set!(equalizer_enabled, true);
+                    self.playback_service.set_equalizer(self.config.equalizer());
+                }
+            }
+
+            Message::SaveEqualizerPreset => {
+                self.dialog_pages
+                    .push_back(DialogPage::SaveEqualizerPreset(String::new()));
+                return widget::text_input::focus(widget::Id::new(EQUALIZER_PRESET_INPUT_ID));
+            }
+
             // Kick off the New Playlist dialog
             Message::NewPlaylist => {
                 self.dialog_pages
@@ -982,25 +1642,90 @@ impl cosmic::Application for AppModel {
                 return widget::text_input::focus(widget::Id::new(NEW_PLAYLIST_INPUT_ID));
             }
 
-            Message::Noop => {}
+            Message::NewSmartPlaylist => {
+                self.dialog_pages
+                    .push_back(DialogPage::SmartPlaylist(SmartPlaylistEditor::new()));
+            }
 
-            // Kick off the Rename Playlist dialog
-            Message::RenamePlaylist => match self.nav.data(self.nav.active()) {
-                Some(Page::Playlist(id)) => {
-                    if self.playlist_service.get(*id).ok().unwrap().is_library() {
-                        return Task::none();
-                    }
-                    let name = self.nav.text(self.nav.active()).unwrap_or("");
-                    self.dialog_pages.push_back(DialogPage::RenamePlaylist {
-                        id: *id,
-                        name: name.into(),
+            Message::EditSmartPlaylist => {
+                let editor = self
+                    .view_playlist
+                    .and_then(|id| self.playlist_service.get(id).ok())
+                    .and_then(|playlist| {
+                        playlist.smart_rules().map(|rules| {
+                            SmartPlaylistEditor::edit(playlist.id(), playlist.name(), rules)
+                        })
                     });
-                    return widget::text_input::focus(widget::Id::new(RENAME_PLAYLIST_INPUT_ID));
+
+                if let Some(editor) = editor {
+                    self.dialog_pages
+                        .push_back(DialogPage::SmartPlaylist(editor));
                 }
-                _ => {}
-            },
+            }
 
-            // Kick off the delete playlist dialog
+            Message::Noop => {}
+
+            Message::OpenPaths(paths) => {
+                return self.read_paths(paths, Message::PathsOpened);
+            }
+
+            Message::EnqueuePaths(paths) => {
+                return self.read_paths(paths, Message::PathsEnqueued);
+            }
+
+            Message::PathsOpened(files) => {
+                if files.is_empty() {
+                    eprintln!("Nothing to play in the opened files");
+                    return Task::none();
+                }
+
+                // Opened files play in a session of their own that isn't saved
+                // as a playlist
+                let mut playlist = Playlist::new(String::new());
+                for track in file_tracks(files) {
+                    playlist.push(track);
+                }
+
+                self.playback_service
+                    .start_session(&playlist, 0, self.state.shuffle);
+                self.playback_service.play();
+            }
+
+            Message::PathsEnqueued(files) => {
+                self.playback_service.enqueue(file_tracks(files));
+            }
+
+            Message::OutputDevice(index) => {
+                // The first entry is the default output
+                let output_device = index
+                    .checked_sub(1)
+                    .and_then(|index| self.playback_service.output_devices().get(index))
+                    .map(|device| device.id.clone());
+                config_set!(output_device, output_device.clone());
+                self.playback_service.set_output_device(output_device);
+            }
+
+            Message::PartialUpdateLibrary => {
+                return self.update_library(ScanMode::Partial);
+            }
+
+            // Kick off the Rename Playlist dialog
+            Message::RenamePlaylist => match self.nav.data(self.nav.active()) {
+                Some(Page::Playlist(id)) => {
+                    if self.playlist_service.get(*id).ok().unwrap().is_library() {
+                        return Task::none();
+                    }
+                    let name = self.nav.text(self.nav.active()).unwrap_or("");
+                    self.dialog_pages.push_back(DialogPage::RenamePlaylist {
+                        id: *id,
+                        name: name.into(),
+                    });
+                    return widget::text_input::focus(widget::Id::new(RENAME_PLAYLIST_INPUT_ID));
+                }
+                _ => {}
+            },
+
+            // Kick off the delete playlist dialog
             Message::DeletePlaylist => {
                 if let Some(Page::Playlist(id)) = self.nav.data(self.nav.active()) {
                     if let Some(p) = self.playlist_service.get(*id).ok() {
@@ -1011,6 +1736,81 @@ impl cosmic::Application for AppModel {
                 }
             }
 
+            Message::ExportPlaylist => {
+                if let Some(id) = self.view_playlist {
+                    self.dialog_pages.push_back(DialogPage::ExportPlaylist {
+                        id,
+                        relative: false,
+                    });
+                }
+            }
+
+            Message::ExportPlaylistTo(id, path, relative) => {
+                match self.playlist_service.export(id, &path, relative) {
+                    Ok(_) => log::info!("Exported playlist to {:?}", path),
+                    Err(err) => eprintln!("Error exporting playlist: {}", err),
+                }
+            }
+
+            Message::ImportPlaylist => {
+                return cosmic::task::future(async move {
+                    let mut dialog =
+                        file_chooser::open::Dialog::new().title(fl!("import-playlist"));
+
+                    for codec in playlist_codec::CODECS {
+                        let filter = codec.extensions().iter().fold(
+                            file_chooser::FileFilter::new(codec.name()),
+                            |filter, ext| filter.glob(format!("*.{}", ext)),
+                        );
+                        dialog = dialog.filter(filter);
+                    }
+
+                    match dialog.open_file().await {
+                        Ok(response) => match response.url().to_file_path() {
+                            Ok(path) => Message::ImportPlaylistFrom(path),
+                            Err(_) => {
+                                eprintln!("Can't convert URL to path.");
+                                Message::Noop
+                            }
+                        },
+                        Err(file_chooser::Error::Cancelled) => Message::Noop,
+                        Err(why) => Message::PlaylistFileError(Arc::new(why)),
+                    }
+                });
+            }
+
+            Message::ImportPlaylistFrom(path) => {
+                match self.playlist_service.import(&path, &self.library) {
+                    Ok((id, unresolved)) => {
+                        self.view_playlist = Some(id);
+
+                        // Rebuild nav preserving order
+                        let items = self.build_ordered_nav_items();
+                        self.rebuild_nav_from_order(items, id);
+
+                        if !unresolved.is_empty() {
+                            let name = self
+                                .playlist_service
+                                .get(id)
+                                .map(|p| p.name().to_string())
+                                .unwrap_or_default();
+
+                            let unresolved = unresolved
+                                .into_iter()
+                                .map(|entry| match entry.title {
+                                    Some(title) => format!("{} ({})", title, entry.location),
+                                    None => entry.location,
+                                })
+                                .collect();
+
+                            self.dialog_pages
+                                .push_back(DialogPage::ImportReport { name, unresolved });
+                        }
+                    }
+                    Err(err) => eprintln!("Error importing playlist: {}", err),
+                }
+            }
+
             Message::MoveNavUp | Message::MoveNavDown => {
                 self.move_active_nav(if matches!(message, Message::MoveNavUp) {
                     -1
@@ -1061,11 +1861,38 @@ impl cosmic::Application for AppModel {
                 }
             }
 
+            Message::PlayNext => {
+                let tracks = self.selected_tracks();
+                self.playback_service.play_next(tracks);
+            }
+
             Message::Previous => {
                 self.playback_service.prev(self.state.repeat_mode.clone());
             }
 
+            Message::QueueClear => {
+                self.playback_service.clear_queue();
+            }
+
+            Message::QueueMoveDown(index) => {
+                self.playback_service.move_queue_entry(index, index + 1);
+            }
+
+            Message::QueueMoveUp(index) => {
+                if index > 0 {
+                    self.playback_service.move_queue_entry(index, index - 1);
+                }
+            }
+
+            Message::QueueRemove(index) => {
+                self.playback_service.remove_queue_entry(index);
+            }
+
             Message::Quit => {
+                if self.config.resume_session {
+                    state_set!(session, self.playback_service.saved_session());
+                    state_set!(session_position, self.playback_service.saved_position());
+                }
                 self.playback_service.stop();
                 process::exit(0);
             }
@@ -1082,6 +1909,19 @@ impl cosmic::Application for AppModel {
                 config_set!(library_paths, library_paths);
             }
 
+            Message::RemoveMissingTracks => {
+                let playlist_id = self
+                    .get_active_playlist()
+                    .filter(|p| !p.is_library() && p.missing_count() > 0)
+                    .map(|p| p.id());
+
+                if let Some(id) = playlist_id {
+                    // Show confirmation dialog
+                    self.dialog_pages
+                        .push_back(DialogPage::RemoveMissingTracks(id));
+                }
+            }
+
             Message::RemoveSelectedFromPlaylist => {
                 // Show confirmation dialog
                 self.dialog_pages
@@ -1115,6 +1955,22 @@ impl cosmic::Application for AppModel {
                 );
             }
 
+            Message::ReplayGainMode(replay_gain_mode) => {
+                config_set!(replay_gain_mode, replay_gain_mode);
+                self.playback_service
+                    .set_replay_gain(self.config.replay_gain());
+            }
+
+            Message::ReplayGainPreamp(replay_gain_preamp) => {
+                config_set!(replay_gain_preamp, replay_gain_preamp);
+                self.playback_service
+                    .set_replay_gain(self.config.replay_gain());
+            }
+
+            Message::ScanWorkers(scan_workers) => {
+                config_set!(scan_workers, scan_workers);
+            }
+
             Message::SelectAll => {
                 if let Some(playlist_id) = self.view_playlist {
                     if let Err(err) = self.playlist_service.select_all(playlist_id) {
@@ -1134,11 +1990,29 @@ impl cosmic::Application for AppModel {
                 config_set!(library_paths, library_paths);
             }
 
+            Message::SetPlaybackRate(playback_rate) => {
+                self.playback_service.set_rate(playback_rate);
+                state_set!(playback_rate, self.playback_service.rate());
+                self.update_mpris();
+            }
+
+            Message::SetLoopEnd => {
+                self.playback_service.set_loop_end();
+            }
+
+            Message::SetLoopStart => {
+                self.playback_service.set_loop_start();
+            }
+
             Message::SetVolume(volume) => {
                 state_set!(volume, volume);
                 self.playback_service.set_volume(volume as f64 / 100.0);
             }
 
+            Message::SleepTimer(timer) => {
+                self.playback_service.set_sleep_timer(timer);
+            }
+
             Message::SliderSeek(time) => {
                 self.playback_service.set_dragging_slider(true);
                 self.playback_service.set_progress(time);
@@ -1147,6 +2021,8 @@ impl cosmic::Application for AppModel {
             Message::Tick => {
                 self.playback_service.validate_session();
 
+                let mut tasks = Vec::new();
+
                 // Process playback events
                 let events = self.playback_service.tick();
                 for event in events {
@@ -1155,15 +2031,41 @@ impl cosmic::Application for AppModel {
                             self.playback_service
                                 .next(self.state.repeat_mode.clone(), self.state.repeat);
                         }
-                        PlaybackEvent::GaplessTrackAdvanced => {
+                        PlaybackEvent::GaplessTrackAdvanced | PlaybackEvent::CrossfadeStarted => {
                             // Session index updates inside playback_service.tick()
                             // Just update MPRIS
                             self.update_mpris();
                         }
-                        PlaybackEvent::Error(err) => {
+                        PlaybackEvent::Error(path, err) => {
                             eprintln!("Playback error: {}", err);
-                            self.playback_service
-                                .next(self.state.repeat_mode.clone(), self.state.repeat);
+
+                            let file = path
+                                .as_ref()
+                                .and_then(|path| path.file_name())
+                                .map(|name| name.to_string_lossy().to_string())
+                                .unwrap_or_default();
+
+                            let notice = if self.playback_service.skip_failed_track(
+                                self.state.repeat_mode.clone(),
+                                self.state.repeat,
+                            ) {
+                                fl!("playback-error-skipped", file = file)
+                            } else {
+                                fl!("playback-error-stopped", file = file)
+                            };
+
+                            tasks.push(
+                                self.toasts
+                                    .push(widget::Toast::new(notice))
+                                    .map(cosmic::Action::App),
+                            );
+                            self.update_mpris();
+                        }
+                        PlaybackEvent::OutputDevicesChanged => {
+                            self.rebuild_output_device_labels();
+                        }
+                        PlaybackEvent::StopPointReached => {
+                            self.update_mpris();
                         }
                         PlaybackEvent::PositionUpdate(_) => {
                             // Position already updated in service
@@ -1196,18 +2098,50 @@ impl cosmic::Application for AppModel {
                             self.playback_service.prev(self.state.repeat_mode.clone());
                         }
                         MprisCommand::Seek(offset_us) => {
-                            let current = self.playback_service.progress();
-                            let new_pos = (current + offset_us as f32 / 1_000_000.0).max(0.0);
-                            self.playback_service.seek(new_pos);
+                            let position =
+                                self.playback_service.position().as_micros() as i64 + offset_us;
+
+                            // Seeking past the end moves on to the next track
+                            match self.playback_service.duration() {
+                                Some(duration) if position >= duration.as_micros() as i64 => {
+                                    self.playback_service
+                                        .next(self.state.repeat_mode.clone(), self.state.repeat);
+                                }
+                                _ => self
+                                    .playback_service
+                                    .seek_position(Duration::from_micros(position.max(0) as u64)),
+                            }
                         }
-                        MprisCommand::SetPosition(pos_us) => {
-                            self.playback_service.seek(pos_us as f32 / 1_000_000.0);
+                        MprisCommand::SetPosition(entry_id, pos_us) => {
+                            // Positions outside the current track are ignored
+                            let in_track =
+                                self.playback_service
+                                    .current_track()
+                                    .is_some_and(|track| track.entry_id == entry_id)
+                                    && pos_us >= 0
+                                    && self.playback_service.duration().is_none_or(|duration| {
+                                        pos_us as u128 <= duration.as_micros()
+                                    });
+                            if in_track {
+                                self.playback_service
+                                    .seek_position(Duration::from_micros(pos_us as u64));
+                            }
                         }
                         MprisCommand::SetVolume(vol) => {
                             let volume = (vol * 100.0).clamp(0.0, 100.0) as i32;
                             state_set!(volume, volume);
                             self.playback_service.set_volume(vol);
                         }
+                        MprisCommand::SetRate(rate) => {
+                            // A rate of 0.0 means pause
+                            if rate <= 0.0 {
+                                self.playback_service.pause();
+                            } else {
+                                self.playback_service.set_rate(rate);
+                                state_set!(playback_rate, self.playback_service.rate());
+                            }
+                            self.update_mpris();
+                        }
                         MprisCommand::SetLoopStatus(status) => match status.as_str() {
                             "None" => {
                                 state_set!(repeat, false);
@@ -1226,13 +2160,80 @@ impl cosmic::Application for AppModel {
                             state_set!(shuffle, shuffle);
                             // update session shuffle as in ToggleShuffle handler
                         }
+                        MprisCommand::AddTrack {
+                            uri,
+                            after,
+                            set_as_current,
+                        } => match self.library_track(&uri) {
+                            Some(track) => {
+                                let entry_id =
+                                    self.playback_service.insert_session_track(track, after);
+                                if let Some(entry_id) = entry_id.filter(|_| set_as_current) {
+                                    self.playback_service.go_to_session_track(entry_id);
+                                }
+                            }
+                            None => eprintln!("Can't add {} to the track list", uri),
+                        },
+                        MprisCommand::RemoveTrack(entry_id) => {
+                            self.playback_service.remove_session_track(entry_id);
+                        }
+                        MprisCommand::GoTo(entry_id) => {
+                            self.playback_service.go_to_session_track(entry_id);
+                        }
+                        MprisCommand::ActivatePlaylist(playlist_id) => {
+                            self.start_playlist_session(playlist_id);
+                            self.playback_service.play();
+                        }
+                        MprisCommand::OpenUri(uri) => match self.library_track(&uri) {
+                            Some(track) => self.open_track(track),
+                            None => eprintln!("Can't open {}", uri),
+                        },
+                        MprisCommand::Open(paths) => {
+                            tasks.push(Task::done(cosmic::Action::App(Message::OpenPaths(paths))));
+                        }
+                        MprisCommand::Enqueue(paths) => {
+                            tasks.push(Task::done(cosmic::Action::App(Message::EnqueuePaths(
+                                paths,
+                            ))));
+                        }
+                        MprisCommand::Raise => {
+                            if let Some(id) = self.core.main_window_id() {
+                                tasks.push(iced::window::gain_focus(id));
+                            }
+                        }
+                        MprisCommand::Quit => {
+                            tasks.push(Task::done(cosmic::Action::App(Message::Quit)));
+                        }
                     }
 
                     self.playback_service
                         .set_repeat_state(self.state.repeat_mode.clone(), self.state.repeat);
                 }
 
+                // Save the session now and then so it also survives a crash
+                if self.config.resume_session
+                    && self.session_saved_at.elapsed()
+                        >= Duration::from_secs(SESSION_SAVE_INTERVAL_SECS)
+                {
+                    self.session_saved_at = Instant::now();
+                    // The track order only changes now and then, the position is
+                    // written on its own so the order isn't rewritten with it
+                    let session = self.playback_service.saved_session();
+                    if session != self.state.session {
+                        state_set!(session, session);
+                    }
+                    let position = self.playback_service.saved_position();
+                    if position != self.state.session_position {
+                        state_set!(session_position, position);
+                    }
+                }
+
+                if let Some(position) = self.playback_service.take_seeked() {
+                    self.mpris_seeked(position);
+                }
                 self.update_mpris();
+
+                return Task::batch(tasks);
             }
 
             Message::ToggleContextPage(context_page) => {
@@ -1263,6 +2264,16 @@ impl cosmic::Application for AppModel {
                 config_set!(list_text_wrap, list_text_wrap);
             }
 
+            Message::ToggleEqualizer(equalizer_enabled) => {
+                config_set!(equalizer_enabled, equalizer_enabled);
+                self.playback_service.set_equalizer(self.config.equalizer());
+            }
+
+            Message::ToggleFastSeek(fast_seek) => {
+                config_set!(fast_seek, fast_seek);
+                self.playback_service.set_fast_seek(fast_seek);
+            }
+
             Message::ToggleListRowAlignTop(list_row_align_top) => {
                 config_set!(list_row_align_top, list_row_align_top);
             }
@@ -1312,8 +2323,48 @@ impl cosmic::Application for AppModel {
                 }
             }
 
+            Message::ToggleSleepFade => {
+                config_set!(sleep_timer_fade, !self.config.sleep_timer_fade);
+                self.playback_service
+                    .set_sleep_fade(self.config.sleep_timer_fade);
+            }
+
+            Message::ToggleStopAfterCurrent => {
+                let stop_after_current = !self.playback_service.stop_after_current();
+                self.playback_service
+                    .set_stop_after_current(stop_after_current);
+            }
+
+            Message::ToggleReplayGainClipping(replay_gain_prevent_clipping) => {
+                config_set!(replay_gain_prevent_clipping, replay_gain_prevent_clipping);
+                self.playback_service
+                    .set_replay_gain(self.config.replay_gain());
+            }
+
+            Message::ToggleResumeSession(resume_session) => {
+                config_set!(resume_session, resume_session);
+                if !resume_session {
+                    state_set!(session, None);
+                    state_set!(session_position, 0.0);
+                }
+            }
+
+            Message::ToggleWatchLibrary(watch_library) => {
+                config_set!(watch_library, watch_library);
+            }
+
             Message::UpdateConfig(config) => {
                 self.config = config;
+                self.playback_service
+                    .set_replay_gain(self.config.replay_gain());
+                self.playback_service.set_crossfade(self.config.crossfade);
+                self.playback_service.set_equalizer(self.config.equalizer());
+                self.rebuild_equalizer_presets();
+                self.playback_service
+                    .set_output_device(self.config.output_device.clone());
+                self.playback_service
+                    .set_sleep_fade(self.config.sleep_timer_fade);
+                self.playback_service.set_fast_seek(self.config.fast_seek);
             }
 
             Message::UpdateDialog(dialog_page) => match dialog_page {
@@ -1333,29 +2384,51 @@ impl cosmic::Application for AppModel {
                 }
 
                 DialogPage::DeleteSelectedFromPlaylist => {}
-            },
 
-            Message::UpdateLibrary => {
-                if self.is_updating {
-                    return Task::none();
+                DialogPage::RemoveMissingTracks(_) => {}
+
+                DialogPage::ExportPlaylist { id, relative } => {
+                    self.dialog_pages
+                        .update_front(DialogPage::ExportPlaylist { id, relative });
                 }
-                self.is_updating = true;
-                self.update_progress = 0.0;
 
-                let library_paths = self.config.library_paths.clone();
-                let xdg_dirs = self.app_xdg_dirs.clone();
+                DialogPage::ImportReport { .. } => {}
 
-                let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+                DialogPage::WriteLoudnessTags(_) => {}
 
-                // Create cancellation token
-                let cancel_token = CancellationToken::new();
-                self.library_update_cancel = Some(cancel_token.clone()); // ← Store it
+                DialogPage::SaveEqualizerPreset(name) => {
+                    self.dialog_pages
+                        .update_front(DialogPage::SaveEqualizerPreset(name));
+                }
 
-                // Spawn the scan with cancellation support
-                LibraryService::scan_library(library_paths, xdg_dirs, tx, cancel_token);
+                DialogPage::SmartPlaylist(editor) => {
+                    self.dialog_pages
+                        .update_front(DialogPage::SmartPlaylist(editor));
+                }
+            },
+
+            Message::UpdateLibrary => {
+                return self.update_library(ScanMode::Full);
+            }
+
+            Message::WriteLoudnessTags => {
+                let paths: Vec<PathBuf> = self
+                    .selected_tracks()
+                    .into_iter()
+                    .map(|t| t.path)
+                    .filter(|path| {
+                        self.library
+                            .media
+                            .get(path)
+                            .and_then(stored_loudness)
+                            .is_some()
+                    })
+                    .collect();
 
-                return cosmic::Task::stream(UnboundedReceiverStream::new(rx))
-                    .map(|progress| cosmic::Action::App(Message::LibraryProgress(progress)));
+                if !paths.is_empty() {
+                    self.dialog_pages
+                        .push_back(DialogPage::WriteLoudnessTags(paths));
+                }
             }
 
             Message::WindowResized(size) => {
@@ -1527,8 +2600,113 @@ impl AppModel {
                     )
                 })
                 .into(),
+            settings::section()
+                .title(fl!("playback"))
+                .add({
+                    let output_device = self
+                        .config
+                        .output_device
+                        .as_ref()
+                        .and_then(|id| {
+                            self.playback_service
+                                .output_devices()
+                                .iter()
+                                .position(|device| device.id == *id)
+                        })
+                        .map_or(0, |index| index + 1);
+
+                    settings::item::builder(fl!("output-device")).control(widget::dropdown(
+                        &self.output_device_labels,
+                        Some(output_device),
+                        Message::OutputDevice,
+                    ))
+                })
+                .add({
+                    settings::item::builder(fl!("resume-session"))
+                        .description(fl!("resume-session-description"))
+                        .control(
+                            toggler(self.config.resume_session)
+                                .on_toggle(Message::ToggleResumeSession),
+                        )
+                })
+                .add({
+                    settings::item::builder(fl!("replay-gain")).control(widget::dropdown(
+                        &self.replay_gain_labels,
+                        ReplayGainMode::ALL
+                            .iter()
+                            .position(|mode| *mode == self.config.replay_gain_mode),
+                        |index| {
+                            Message::ReplayGainMode(
+                                ReplayGainMode::ALL.get(index).copied().unwrap_or_default(),
+                            )
+                        },
+                    ))
+                })
+                .add({
+                    settings::item::builder(fl!("replay-gain-preamp"))
+                        .description(fl!("replay-gain-preamp-description"))
+                        .control(widget::dropdown(
+                            &self.replay_gain_preamp_labels,
+                            REPLAY_GAIN_PREAMP_CHOICES
+                                .iter()
+                                .position(|db| *db == self.config.replay_gain_preamp),
+                            |index| {
+                                Message::ReplayGainPreamp(
+                                    REPLAY_GAIN_PREAMP_CHOICES
+                                        .get(index)
+                                        .copied()
+                                        .unwrap_or_default(),
+                                )
+                            },
+                        ))
+                })
+                .add({
+                    settings::item::builder(fl!("replay-gain-prevent-clipping")).control(
+                        toggler(self.config.replay_gain_prevent_clipping)
+                            .on_toggle(Message::ToggleReplayGainClipping),
+                    )
+                })
+                .add({
+                    settings::item::builder(fl!("fast-seek"))
+                        .description(fl!("fast-seek-description"))
+                        .control(toggler(self.config.fast_seek).on_toggle(Message::ToggleFastSeek))
+                })
+                .add({
+                    settings::item::builder(fl!("crossfade"))
+                        .description(fl!("crossfade-description"))
+                        .control(widget::dropdown(
+                            &self.crossfade_labels,
+                            CROSSFADE_CHOICES
+                                .iter()
+                                .position(|secs| *secs == self.config.crossfade),
+                            |index| {
+                                Message::Crossfade(
+                                    CROSSFADE_CHOICES.get(index).copied().unwrap_or_default(),
+                                )
+                            },
+                        ))
+                })
+                .into(),
             settings::section()
                 .title(fl!("library"))
+                .add({
+                    settings::item::builder(fl!("watch-library")).control(
+                        toggler(self.config.watch_library).on_toggle(Message::ToggleWatchLibrary),
+                    )
+                })
+                .add({
+                    settings::item::builder(fl!("scan-workers")).control(widget::dropdown(
+                        &self.scan_worker_labels,
+                        SCAN_WORKER_CHOICES
+                            .iter()
+                            .position(|n| *n == self.config.scan_workers),
+                        |index| {
+                            Message::ScanWorkers(
+                                SCAN_WORKER_CHOICES.get(index).copied().unwrap_or_default(),
+                            )
+                        },
+                    ))
+                })
                 .add(library_column)
                 .into(),
         ])
@@ -1631,11 +2809,303 @@ impl AppModel {
         column.into()
     }
 
+    /// Play queue panel
+    fn queue_panel(&self) -> Element<'_, Message> {
+        let cosmic_theme::Spacing {
+            space_xxs,
+            space_xs,
+            ..
+        } = theme::active().cosmic().spacing;
+
+        let queue = self.playback_service.queue();
+
+        let mut column = widget::column().spacing(space_xs);
+
+        if queue.is_empty() {
+            return column.push(widget::text(fl!("queue-empty"))).into();
+        }
+
+        for (i, t) in queue.iter().enumerate() {
+            let title = t
+                .metadata
+                .title
+                .clone()
+                .unwrap_or_else(|| t.path.to_string_lossy().to_string());
+            let artist = t.metadata.artist.clone().unwrap_or_default();
+
+            let row = widget::row()
+                .spacing(space_xxs)
+                .align_y(Alignment::Center)
+                .push(
+                    widget::column()
+                        .width(Length::Fill)
+                        .push(widget::text(title))
+                        .push(widget::text::caption(artist)),
+                )
+                .push(
+                    widget::button::icon(widget::icon::from_name("go-up-symbolic"))
+                        .on_press_maybe((i > 0).then_some(Message::QueueMoveUp(i))),
+                )
+                .push(
+                    widget::button::icon(widget::icon::from_name("go-down-symbolic"))
+                        .on_press_maybe((i + 1 < queue.len()).then_some(Message::QueueMoveDown(i))),
+                )
+                .push(
+                    widget::button::icon(widget::icon::from_name("list-remove-symbolic"))
+                        .on_press(Message::QueueRemove(i)),
+                );
+
+            if i > 0 {
+                column = column.push(widget::divider::horizontal::light())
+            }
+
+            column = column.push(row);
+        }
+
+        column
+            .push(widget::button::standard(fl!("clear-queue")).on_press(Message::QueueClear))
+            .into()
+    }
+
+    /// Equalizer bypass, presets and band sliders
+    fn equalizer_panel(&self) -> Element<'_, Message> {
+        let cosmic_theme::Spacing {
+            space_xxs,
+            space_xs,
+            space_s,
+            ..
+        } = theme::active().cosmic().spacing;
+
+        let gains = &self.config.equalizer_gains;
+        let preset = self
+            .equalizer_presets
+            .iter()
+            .position(|preset| preset.gains == *gains);
+        let is_user_preset = self
+            .config
+            .equalizer_presets
+            .iter()
+            .any(|preset| preset.gains == *gains);
+
+        let mut bands = widget::column().spacing(space_xxs);
+        for (band, frequency) in EQUALIZER_BAND_FREQUENCIES.iter().enumerate() {
+            let gain = gains[band];
+
+            bands = bands.push(
+                widget::row()
+                    .spacing(space_xs)
+                    .align_y(Alignment::Center)
+                    .push(
+                        widget::text(equalizer::band_label(*frequency)).width(Length::Fixed(64.0)),
+                    )
+                    .push(widget::slider(
+                        -EQUALIZER_MAX_GAIN..=EQUALIZER_MAX_GAIN,
+                        gain,
+                        move |gain| Message::EqualizerBand(band, gain),
+                    ))
+                    .push(
+                        widget::text(format!("{:+} dB", gain))
+                            .width(Length::Fixed(56.0))
+                            .align_x(Horizontal::Right),
+                    ),
+            );
+        }
+
+        widget::column()
+            .spacing(space_s)
+            .push(
+                settings::section()
+                    .add(settings::item::builder(fl!("equalizer-enabled")).control(
+                        toggler(self.config.equalizer_enabled).on_toggle(Message::ToggleEqualizer),
+                    ))
+                    .add(
+                        settings::item::builder(fl!("preset")).control(widget::dropdown(
+                            &self.equalizer_preset_labels,
+                            preset,
+                            Message::EqualizerPreset,
+                        )),
+                    ),
+            )
+            .push(bands)
+            .push(
+                widget::row()
+                    .spacing(space_xs)
+                    .push(
+                        widget::button::standard(fl!("save-preset"))
+                            .on_press(Message::SaveEqualizerPreset),
+                    )
+                    .push(
+                        widget::button::destructive(fl!("delete-preset")).on_press_maybe(
+                            is_user_preset.then_some(Message::DeleteEqualizerPreset),
+                        ),
+                    ),
+            )
+            .into()
+    }
+
+    /// Labels for the default output followed by the available devices
+    fn rebuild_output_device_labels(&mut self) {
+        self.output_device_labels = std::iter::once(fl!("default-output"))
+            .chain(
+                self.playback_service
+                    .output_devices()
+                    .iter()
+                    .map(|device| device.name.clone()),
+            )
+            .collect();
+    }
+
+    /// Built-in followed by user equalizer presets, with their dropdown labels
+    fn rebuild_equalizer_presets(&mut self) {
+        self.equalizer_presets = equalizer::builtin_presets();
+        self.equalizer_presets
+            .extend(self.config.equalizer_presets.iter().cloned());
+        self.equalizer_preset_labels = self
+            .equalizer_presets
+            .iter()
+            .map(|preset| preset.name.clone())
+            .collect();
+    }
+
+    /// Selected tracks of the viewed playlist, in playlist order
+    fn selected_tracks(&self) -> Vec<Track> {
+        self.get_active_playlist()
+            .map(|playlist| playlist.selected().into_iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
     /// Updates the cosmic config, in particular the theme
     fn update_config(&mut self) -> Task<cosmic::Action<Message>> {
         cosmic::command::set_theme(self.config.app_theme.theme())
     }
 
+    /// Start a library scan in the background and stream its progress
+    fn update_library(&mut self, mode: ScanMode) -> Task<cosmic::Action<Message>> {
+        if self.is_updating {
+            return Task::none();
+        }
+        self.is_updating = true;
+        self.update_progress = 0.0;
+
+        // A scan covers anything the watcher has reported so far
+        self.pending_library_changes = LibraryChanges::default();
+        self.applying_library_changes = None;
+
+        let library_paths = self.config.library_paths.clone();
+        let xdg_dirs = self.app_xdg_dirs.clone();
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // Create cancellation token
+        let cancel_token = CancellationToken::new();
+        self.library_update_cancel = Some(cancel_token.clone());
+
+        // Spawn the scan with cancellation support
+        LibraryService::scan_library(
+            library_paths,
+            self.library.clone(),
+            mode,
+            LibraryService::worker_count(self.config.scan_workers),
+            xdg_dirs,
+            tx,
+            cancel_token,
+        );
+
+        cosmic::Task::stream(UnboundedReceiverStream::new(rx))
+            .map(|progress| cosmic::Action::App(Message::LibraryProgress(progress)))
+    }
+
+    /// Analyze the loudness of groups of files in the background
+    fn analyze_loudness(
+        &mut self,
+        groups: Vec<Vec<PathBuf>>,
+        albums: bool,
+    ) -> Task<cosmic::Action<Message>> {
+        if self.is_updating || groups.is_empty() {
+            return Task::none();
+        }
+        self.is_updating = true;
+        self.is_analyzing = true;
+        self.update_progress = 0.0;
+        self.update_percent = 0.0;
+        self.update_progress_display = fl!("analyzing-loudness");
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let cancel_token = CancellationToken::new();
+        self.library_update_cancel = Some(cancel_token.clone());
+
+        LoudnessService::analyze(groups, albums, tx, cancel_token);
+
+        cosmic::Task::stream(UnboundedReceiverStream::new(rx))
+            .map(|progress| cosmic::Action::App(Message::LoudnessProgress(progress)))
+    }
+
+    /// Library files of every album that has a selected track, grouped by album
+    /// Selected tracks without an album tag make up a group of their own
+    fn selected_album_groups(&self) -> Vec<Vec<PathBuf>> {
+        let album_key = |metadata: &MediaMetaData| {
+            let artist = metadata.album_artist.clone().or(metadata.artist.clone());
+            metadata.album.clone().map(|album| (artist, album))
+        };
+
+        let mut keys = HashSet::new();
+        let mut groups = Vec::new();
+
+        for track in self.selected_tracks() {
+            if track.missing {
+                continue;
+            }
+            match album_key(&track.metadata) {
+                Some(key) => {
+                    keys.insert(key);
+                }
+                None => groups.push(vec![track.path]),
+            }
+        }
+
+        let mut albums: HashMap<_, Vec<PathBuf>> = HashMap::new();
+        for (path, metadata) in &self.library.media {
+            if let Some(key) = album_key(metadata).filter(|key| keys.contains(key)) {
+                albums.entry(key).or_default().push(path.clone());
+            }
+        }
+
+        groups.extend(albums.into_values());
+        groups
+    }
+
+    /// Apply file changes reported by the library watcher, unless an update is
+    /// already running. In that case they stay pending until it completes.
+    fn apply_pending_library_changes(&mut self) -> Task<cosmic::Action<Message>> {
+        if self.is_updating || self.pending_library_changes.is_empty() {
+            return Task::none();
+        }
+        self.is_updating = true;
+        self.update_progress = 0.0;
+
+        let changes = std::mem::take(&mut self.pending_library_changes);
+        self.applying_library_changes = Some(changes.clone());
+        let xdg_dirs = self.app_xdg_dirs.clone();
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let cancel_token = CancellationToken::new();
+        self.library_update_cancel = Some(cancel_token.clone());
+
+        LibraryService::apply_changes(
+            changes,
+            self.library.clone(),
+            LibraryService::worker_count(self.config.scan_workers),
+            xdg_dirs,
+            tx,
+            cancel_token,
+        );
+
+        cosmic::Task::stream(UnboundedReceiverStream::new(rx))
+            .map(|progress| cosmic::Action::App(Message::LibraryProgress(progress)))
+    }
+
     /// Load library and playlists
     pub fn load_data(&mut self) -> Task<cosmic::Action<Message>> {
         // Load library from disk
@@ -1663,10 +3133,23 @@ impl AppModel {
             return Task::none();
         }
 
+        // Flag playlist entries that point at files that are gone
+        let missing = self.playlist_service.validate(&self.library);
+        if missing > 0 {
+            log::info!("{} playlist tracks are missing", missing);
+        }
+
+        self.playlist_service.refresh_smart(&self.library);
+
         let playlist_ids: Vec<u32> = self.playlist_service.all().iter().map(|p| p.id()).collect();
 
         for id in playlist_ids {
             if let Ok(playlist) = self.playlist_service.get_mut(id) {
+                // Smart playlists keep the order from their rules
+                if playlist.is_smart() {
+                    continue;
+                }
+
                 playlist.sort(
                     self.state.sort_by.clone(),
                     self.state.sort_direction.clone(),
@@ -1730,6 +3213,24 @@ impl AppModel {
         // Rebuild nav once
         self.rebuild_nav_from_order(items, active_id);
 
+        // Pick up where the last session left off
+        if self.config.resume_session {
+            if let Some(saved) = self.state.session.clone() {
+                match self.playlist_service.get(saved.playlist_id) {
+                    Ok(playlist) => {
+                        let position = self.state.session_position;
+                        if !self
+                            .playback_service
+                            .restore_session(playlist, &saved, position)
+                        {
+                            log::info!("No tracks left to resume in the last session");
+                        }
+                    }
+                    Err(_) => log::info!("The last session's playlist no longer exists"),
+                }
+            }
+        }
+
         self.initial_load_complete = true;
         Task::none()
     }
@@ -1748,83 +3249,86 @@ impl AppModel {
             (true, RepeatMode::All) => "Playlist".to_string(),
         };
         state.volume = self.state.volume as f64 / 100.0;
-        state.position = (self.playback_service.progress() * 1_000_000.0) as i64;
-
-        // Build metadata
-        let mut meta = HashMap::new();
-        if let Some(now_playing) = self.playback_service.now_playing() {
-            // Track ID
-            let track_id = format!(
-                "/com/galacticpirateradio/etherealwaves/track/{}",
-                now_playing
-                    .id
-                    .clone()
-                    .unwrap_or_else(|| "unknown".to_string())
-                    .replace("-", "_")
-            );
+        state.rate = self.playback_service.rate();
+        state.position = self.playback_service.position().as_micros() as i64;
 
-            if let Ok(obj_path) = zbus::zvariant::ObjectPath::try_from(track_id) {
-                meta.insert(
-                    "mpris:trackid".to_string(),
-                    zbus::zvariant::Value::new(obj_path).into(),
-                );
-            }
-
-            // Title
-            if let Some(title) = &now_playing.title {
-                meta.insert(
-                    "xesam:title".to_string(),
-                    zbus::zvariant::Value::new(title.clone()).into(),
-                );
-            }
-
-            // Artist (as array)
-            if let Some(artist) = &now_playing.artist {
-                meta.insert(
-                    "xesam:artist".to_string(),
-                    zbus::zvariant::Value::new(vec![artist.clone()]).into(),
-                );
-            }
-
-            // Album
-            if let Some(album) = &now_playing.album {
-                meta.insert(
-                    "xesam:album".to_string(),
-                    zbus::zvariant::Value::new(album.clone()).into(),
-                );
-            }
+        let artwork_dir = self.image_store.artwork_dir();
+        let current = self
+            .playback_service
+            .now_playing()
+            .and(self.playback_service.current_track());
+        state.metadata = current
+            .map(|track| mpris::track_metadata(track, artwork_dir))
+            .unwrap_or_default();
+
+        // What the controls can do right now
+        let capabilities = Capabilities {
+            can_play: current.is_some() || self.view_playlist.is_some(),
+            can_pause: current.is_some(),
+            can_seek: current.is_some(),
+            can_go_next: self.playback_service.has_next(),
+            can_go_previous: self.playback_service.has_previous(),
+        };
+        let capabilities_changed = capabilities != state.capabilities;
+        state.capabilities = capabilities;
+
+        // The track list follows the session order
+        let session = self.playback_service.session();
+        let tracks: Vec<u32> = session
+            .map(|session| session.order.iter().map(|t| t.entry_id).collect())
+            .unwrap_or_default();
+        let current_track = session
+            .filter(|_| !self.playback_service.is_playing_from_queue())
+            .and_then(|session| session.order.get(session.index))
+            .map(|t| t.entry_id);
+
+        let mut track_list_signal = None;
+        if tracks != state.tracks {
+            let change = TrackListChange::between(&state.tracks, &tracks);
+            state.track_metadata = session
+                .map(|session| {
+                    session
+                        .order
+                        .iter()
+                        .map(|t| (t.entry_id, mpris::track_metadata(t, artwork_dir)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            state.tracks = tracks;
 
-            // Duration (in microseconds)
-            if let Some(duration) = now_playing.duration {
-                meta.insert(
-                    "mpris:length".to_string(),
-                    zbus::zvariant::Value::new((duration * 1_000_000.0) as i64).into(),
-                );
-            }
+            let added = match change {
+                TrackListChange::Added(id, _) => state.track_metadata.get(&id).cloned(),
+                _ => None,
+            };
+            track_list_signal = Some((change, added, state.tracks.clone()));
+        }
 
-            // Album art URL
-            if let Some(id) = &now_playing.id {
-                // Try to find artwork file using the track ID
-                let artwork_filename = format!("{}.jpg", id);
-                let base_dirs = xdg::BaseDirectories::with_prefix("ethereal-waves");
+        let playlists: Vec<MprisPlaylist> = self
+            .playlist_service
+            .ordered(&self.state.playlist_nav_order)
+            .iter()
+            .map(|p| MprisPlaylist {
+                id: p.id(),
+                name: p.name().to_string(),
+            })
+            .collect();
+        let renamed: Vec<MprisPlaylist> = playlists
+            .iter()
+            .filter(|p| {
+                state
+                    .playlists
+                    .iter()
+                    .any(|old| old.id == p.id && old.name != p.name)
+            })
+            .cloned()
+            .collect();
+        let active_playlist = session.map(|session| session.playlist_id);
 
-                if let Some(artwork_path) =
-                    base_dirs.find_cache_file(format!("artwork/{}", artwork_filename))
-                {
-                    if artwork_path.exists() {
-                        meta.insert(
-                            "mpris:artUrl".to_string(),
-                            zbus::zvariant::Value::new(format!(
-                                "file://{}",
-                                artwork_path.to_string_lossy()
-                            ))
-                            .into(),
-                        );
-                    }
-                }
-            }
-        }
-        state.metadata = meta;
+        let playlist_count_changed = playlists.len() != state.playlists.len();
+        let active_playlist_changed = active_playlist != state.active_playlist
+            || renamed.iter().any(|p| Some(p.id) == active_playlist);
+        state.playlists = playlists;
+        state.active_playlist = active_playlist;
 
         // Drop the lock before spawning async task
         drop(state);
@@ -1860,28 +3364,187 @@ impl AppModel {
                     .loop_status_changed(&signal_ctx)
                     .await;
                 let _ = iface_ref.get_mut().await.volume_changed(&signal_ctx).await;
+                let _ = iface_ref.get_mut().await.rate_changed(&signal_ctx).await;
+
+                if capabilities_changed {
+                    let iface = iface_ref.get_mut().await;
+                    let _ = iface.can_play_changed(&signal_ctx).await;
+                    let _ = iface.can_pause_changed(&signal_ctx).await;
+                    let _ = iface.can_seek_changed(&signal_ctx).await;
+                    let _ = iface.can_go_next_changed(&signal_ctx).await;
+                    let _ = iface.can_go_previous_changed(&signal_ctx).await;
+                }
+            }
+
+            if !renamed.is_empty() || playlist_count_changed || active_playlist_changed {
+                if let Ok(iface_ref) = conn
+                    .object_server()
+                    .interface::<_, MediaPlayer2Playlists>("/org/mpris/MediaPlayer2")
+                    .await
+                {
+                    let emitter = iface_ref.signal_emitter();
+
+                    for playlist in &renamed {
+                        let _ =
+                            MediaPlayer2Playlists::playlist_changed(emitter, playlist.to_struct())
+                                .await;
+                    }
+                    if playlist_count_changed {
+                        let _ = iface_ref
+                            .get_mut()
+                            .await
+                            .playlist_count_changed(emitter)
+                            .await;
+                    }
+                    if active_playlist_changed {
+                        let _ = iface_ref
+                            .get_mut()
+                            .await
+                            .active_playlist_changed(emitter)
+                            .await;
+                    }
+                }
+            }
+
+            let Some((change, added, tracks)) = track_list_signal else {
+                return;
+            };
+            if let Ok(iface_ref) = conn
+                .object_server()
+                .interface::<_, MediaPlayer2TrackList>("/org/mpris/MediaPlayer2")
+                .await
+            {
+                let emitter = iface_ref.signal_emitter();
+                let _ = match change {
+                    TrackListChange::Added(_, after) => {
+                        MediaPlayer2TrackList::track_added(
+                            emitter,
+                            added.unwrap_or_default(),
+                            mpris::track_id_or_none(after),
+                        )
+                        .await
+                    }
+                    TrackListChange::Removed(id) => {
+                        MediaPlayer2TrackList::track_removed(emitter, mpris::track_id(id)).await
+                    }
+                    TrackListChange::Replaced => {
+                        MediaPlayer2TrackList::track_list_replaced(
+                            emitter,
+                            tracks.into_iter().map(mpris::track_id).collect(),
+                            mpris::track_id_or_none(current_track),
+                        )
+                        .await
+                    }
+                };
+            }
+        });
+    }
+
+    /// Emit `Seeked` after a seek or a jump within the track
+    fn mpris_seeked(&self, position: Duration) {
+        let Some(conn) = &self.mpris_connection else {
+            return;
+        };
+
+        let conn = conn.clone();
+        tokio::spawn(async move {
+            if let Ok(iface_ref) = conn
+                .object_server()
+                .interface::<_, MediaPlayer2Player>("/org/mpris/MediaPlayer2")
+                .await
+            {
+                let _ = MediaPlayer2Player::seeked(
+                    iface_ref.signal_emitter(),
+                    position.as_micros() as i64,
+                )
+                .await;
             }
         });
     }
 
+    /// Find a library track by its file URI
+    fn library_track(&self, uri: &str) -> Option<Track> {
+        let path = url::Url::parse(uri).ok()?.to_file_path().ok()?;
+        self.playlist_service
+            .get_library()
+            .ok()?
+            .tracks()
+            .iter()
+            .find(|t| t.path == path)
+            .cloned()
+    }
+
+    /// Play a library track right away, after the current one if a session is
+    /// running or from the library otherwise
+    fn open_track(&mut self, track: Track) {
+        let after = self
+            .playback_service
+            .session()
+            .and_then(|session| session.order.get(session.index))
+            .map(|t| t.entry_id);
+
+        if let Some(entry_id) = self
+            .playback_service
+            .insert_session_track(track.clone(), after)
+        {
+            self.playback_service.go_to_session_track(entry_id);
+        } else if let Ok(library) = self.playlist_service.get_library() {
+            if let Some(index) = library
+                .tracks()
+                .iter()
+                .position(|t| t.entry_id == track.entry_id)
+            {
+                self.playback_service
+                    .start_session(library, index, self.state.shuffle);
+                self.playback_service.play();
+            }
+        }
+    }
+
+    /// Read files, folders and playlist files in the background, outside the
+    /// library too
+    fn read_paths(
+        &self,
+        paths: Vec<PathBuf>,
+        on_read: fn(Vec<(PathBuf, MediaMetaData)>) -> Message,
+    ) -> Task<cosmic::Action<Message>> {
+        let library = self.library.clone();
+        let xdg_dirs = self.app_xdg_dirs.clone();
+
+        cosmic::task::future(async move {
+            let files = tokio::task::spawn_blocking(move || {
+                LibraryService::open_files(paths, &library, &xdg_dirs)
+            })
+            .await
+            .unwrap_or_default();
+
+            on_read(files)
+        })
+    }
+
     fn start_session_maybe(&mut self) {
         if self.playback_service.session().is_none() {
             if let Some(playlist_id) = self.view_playlist {
-                if let Ok(playlist) = self.playlist_service.get(playlist_id) {
-                    let start_index = if self.state.shuffle && !playlist.tracks().is_empty() {
-                        use rand::Rng;
-                        rand::rng().random_range(0..playlist.tracks().len())
-                    } else {
-                        0
-                    };
-
-                    self.playback_service
-                        .start_session(playlist, start_index, self.state.shuffle);
-                }
+                self.start_playlist_session(playlist_id);
             }
         }
     }
 
+    /// Start a session from the top of a playlist, or a random track when shuffling
+    fn start_playlist_session(&mut self, playlist_id: PlaylistId) {
+        if let Ok(playlist) = self.playlist_service.get(playlist_id) {
+            let start_index = if self.state.shuffle && !playlist.tracks().is_empty() {
+                use rand::Rng;
+                rand::rng().random_range(0..playlist.tracks().len())
+            } else {
+                0
+            };
+
+            self.playback_service
+                .start_session(playlist, start_index, self.state.shuffle);
+        }
+    }
+
     fn rebuild_nav_from_order(&mut self, items: Vec<NavPlaylistItem>, activate_id: u32) {
         self.nav.clear();
 
@@ -1896,14 +3559,20 @@ impl AppModel {
 
         // Add the playlists
         for (i, item) in items.iter().enumerate() {
-            if self.playlist_service.get(item.id).is_err() {
+            let Ok(playlist) = self.playlist_service.get(item.id) else {
                 continue;
-            }
+            };
+
+            let icon = if playlist.is_smart() {
+                "system-search-symbolic"
+            } else {
+                "playlist-symbolic"
+            };
 
             self.nav
                 .insert()
                 .text(item.name.clone())
-                .icon(widget::icon::from_name("playlist-symbolic"))
+                .icon(widget::icon::from_name(icon))
                 .data(Page::Playlist(item.id))
                 .divider_above(i == 0);
         }
@@ -2087,13 +3756,11 @@ impl AppModel {
         view_model.is_playing_playlist
             && self
                 .playback_service
-                .session()
-                .and_then(|session| {
-                    session.order.get(session.index).and_then(|playing_track| {
-                        let playing_id = playing_track.metadata.id.clone()?;
-                        let current_id = track.metadata.id.clone()?;
-                        Some(playing_id == current_id && playing_track.entry_id == track.entry_id)
-                    })
+                .current_track()
+                .and_then(|playing_track| {
+                    let playing_id = playing_track.metadata.id.clone()?;
+                    let current_id = track.metadata.id.clone()?;
+                    Some(playing_id == current_id && playing_track.entry_id == track.entry_id)
                 })
                 .unwrap_or(false)
     }
@@ -2202,6 +3869,20 @@ impl AppModel {
                 }
                 DialogPage::DeletePlaylist(_) => {}
                 DialogPage::DeleteSelectedFromPlaylist => {}
+                DialogPage::RemoveMissingTracks(_) => {}
+                DialogPage::ExportPlaylist { .. } => {}
+                DialogPage::ImportReport { .. } => {}
+                DialogPage::WriteLoudnessTags(_) => {}
+                DialogPage::SaveEqualizerPreset(name) => {
+                    if key == Key::Named(Named::Enter) && name.trim().is_empty() {
+                        return Task::none();
+                    }
+                }
+                DialogPage::SmartPlaylist(editor) => {
+                    if key == Key::Named(Named::Enter) && !editor.is_valid() {
+                        return Task::none();
+                    }
+                }
             }
 
             if key == Key::Named(Named::Enter) {
@@ -2241,6 +3922,19 @@ impl AppModel {
     }
 }
 
+/// Tracks for files read with `LibraryService::open_files`
+fn file_tracks(files: Vec<(PathBuf, MediaMetaData)>) -> Vec<Track> {
+    files
+        .into_iter()
+        .map(|(path, metadata)| {
+            let mut track = Track::new();
+            track.path = path;
+            track.metadata = metadata;
+            track
+        })
+        .collect()
+}
+
 #[derive(Clone)]
 struct NavPlaylistItem {
     id: u32,
@@ -2253,6 +3947,8 @@ pub struct Flags {
     pub config_handler: Option<cosmic_config::Config>,
     pub state_handler: Option<cosmic_config::Config>,
     pub state: State,
+    /// Files, folders and playlist files to play in a temporary session
+    pub open_paths: Vec<PathBuf>,
 }
 
 /// The page to display in the application.
@@ -2268,6 +3964,8 @@ pub enum ContextPage {
     About,
     Settings,
     TrackInfo,
+    Queue,
+    Equalizer,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -2275,18 +3973,38 @@ pub enum MenuAction {
     About,
     AddSelectedToPlaylist(PlaylistId),
     AddNowPlayingToPlaylist(PlaylistId),
+    ClearLoop,
+    RemoveMissingTracks,
     RemoveSelectedFromPlaylist,
     DeletePlaylist,
+    EditSmartPlaylist,
+    ExportPlaylist,
+    ImportPlaylist,
+    LocateMissingTracks,
     MoveNavDown,
     MoveNavUp,
     NewPlaylist,
+    NewSmartPlaylist,
+    PartialUpdateLibrary,
+    AnalyzeLoudness,
+    AnalyzeAlbumLoudness,
+    WriteLoudnessTags,
+    PlayNext,
+    AddToQueue,
+    QueuePanel,
+    EqualizerPanel,
     Quit,
     RenamePlaylist,
     SelectAll,
+    SetLoopEnd,
+    SetLoopStart,
     Settings,
+    SleepTimer(Option<SleepTimer>),
     ToggleRepeat,
     ToggleRepeatMode,
     ToggleShuffle,
+    ToggleSleepFade,
+    ToggleStopAfterCurrent,
     TrackInfoPanel,
     UpdateLibrary,
     ZoomIn,
@@ -2301,18 +4019,38 @@ impl menu::action::MenuAction for MenuAction {
             MenuAction::About => Message::ToggleContextPage(ContextPage::About),
             MenuAction::AddSelectedToPlaylist(id) => Message::AddSelectedToPlaylist(*id),
             MenuAction::AddNowPlayingToPlaylist(id) => Message::AddNowPlayingToPlaylist(*id),
+            MenuAction::ClearLoop => Message::ClearLoop,
             MenuAction::RemoveSelectedFromPlaylist => Message::RemoveSelectedFromPlaylist,
             MenuAction::DeletePlaylist => Message::DeletePlaylist,
+            MenuAction::EditSmartPlaylist => Message::EditSmartPlaylist,
+            MenuAction::ExportPlaylist => Message::ExportPlaylist,
+            MenuAction::ImportPlaylist => Message::ImportPlaylist,
+            MenuAction::LocateMissingTracks => Message::LocateMissingTracks,
+            MenuAction::RemoveMissingTracks => Message::RemoveMissingTracks,
             MenuAction::MoveNavDown => Message::MoveNavDown,
             MenuAction::MoveNavUp => Message::MoveNavUp,
             MenuAction::NewPlaylist => Message::NewPlaylist,
+            MenuAction::NewSmartPlaylist => Message::NewSmartPlaylist,
+            MenuAction::PartialUpdateLibrary => Message::PartialUpdateLibrary,
+            MenuAction::AnalyzeLoudness => Message::AnalyzeLoudness,
+            MenuAction::AnalyzeAlbumLoudness => Message::AnalyzeAlbumLoudness,
+            MenuAction::WriteLoudnessTags => Message::WriteLoudnessTags,
+            MenuAction::PlayNext => Message::PlayNext,
+            MenuAction::AddToQueue => Message::AddToQueue,
+            MenuAction::QueuePanel => Message::ToggleContextPage(ContextPage::Queue),
+            MenuAction::EqualizerPanel => Message::ToggleContextPage(ContextPage::Equalizer),
             MenuAction::RenamePlaylist => Message::RenamePlaylist,
             MenuAction::Quit => Message::Quit,
             MenuAction::SelectAll => Message::SelectAll,
+            MenuAction::SetLoopEnd => Message::SetLoopEnd,
+            MenuAction::SetLoopStart => Message::SetLoopStart,
             MenuAction::Settings => Message::ToggleContextPage(ContextPage::Settings),
             MenuAction::ToggleRepeat => Message::ToggleRepeat,
             MenuAction::ToggleRepeatMode => Message::ToggleRepeatMode,
             MenuAction::ToggleShuffle => Message::ToggleShuffle,
+            MenuAction::SleepTimer(timer) => Message::SleepTimer(*timer),
+            MenuAction::ToggleSleepFade => Message::ToggleSleepFade,
+            MenuAction::ToggleStopAfterCurrent => Message::ToggleStopAfterCurrent,
             MenuAction::TrackInfoPanel => Message::ToggleContextPage(ContextPage::TrackInfo),
             MenuAction::UpdateLibrary => Message::UpdateLibrary,
             MenuAction::ZoomIn => Message::ZoomIn,
@@ -2327,6 +4065,15 @@ pub enum DialogPage {
     RenamePlaylist { id: u32, name: String },
     DeletePlaylist(u32),
     DeleteSelectedFromPlaylist,
+    RemoveMissingTracks(u32),
+    ExportPlaylist { id: u32, relative: bool },
+    ImportReport {
+        name: String,
+        unresolved: Vec<String>,
+    },
+    SmartPlaylist(SmartPlaylistEditor),
+    WriteLoudnessTags(Vec<PathBuf>),
+    SaveEqualizerPreset(String),
 }
 
 pub struct DialogPages {
@@ -2384,6 +4131,7 @@ pub enum SortDirection {
 pub enum PlaylistKind {
     Library,
     User,
+    Smart(SmartRules),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -2425,3 +4173,14 @@ pub struct ListViewModel {
     pub row_align: Alignment,
     pub sort_direction_icon: String,
 }
+
+/// ReplayGain values stored in the library for a file, if it has been analyzed
+/// or tagged
+fn stored_loudness(metadata: &MediaMetaData) -> Option<Loudness> {
+    Some(Loudness {
+        track_gain: metadata.track_gain?,
+        track_peak: metadata.track_peak?,
+        album_gain: metadata.album_gain,
+        album_peak: metadata.album_peak,
+    })
+}
@@ -1,7 +1,8 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use crate::app::{AppModel, SortBy, SortDirection};
-use crate::playback_state::RepeatMode;
+use crate::equalizer::{EqualizerGains, EqualizerPreset};
+use crate::playback_state::{RepeatMode, ReplayGainMode, ReplayGainSettings, SavedSession};
 use cosmic::{
     Application,
     cosmic_config::{self, CosmicConfigEntry, cosmic_config_derive::CosmicConfigEntry},
@@ -37,6 +38,25 @@ pub struct Config {
     pub library_paths: HashSet<String>,
     pub list_text_wrap: bool,
     pub list_row_align_top: bool,
+    pub watch_library: bool,
+    pub scan_workers: usize,
+    pub resume_session: bool,
+    pub replay_gain_mode: ReplayGainMode,
+    /// Pre-amp in dB
+    pub replay_gain_preamp: i32,
+    pub replay_gain_prevent_clipping: bool,
+    /// Crossfade length in seconds, 0 is off
+    pub crossfade: u32,
+    pub equalizer_enabled: bool,
+    pub equalizer_gains: EqualizerGains,
+    /// Presets saved by the user, the built-in ones aren't stored
+    pub equalizer_presets: Vec<EqualizerPreset>,
+    /// Output device id, `None` for the default output
+    pub output_device: Option<String>,
+    /// Fade out before the sleep timer stops playback
+    pub sleep_timer_fade: bool,
+    /// Seek to keyframes instead of the exact position
+    pub fast_seek: bool,
 }
 
 impl Config {
@@ -58,6 +78,19 @@ impl Config {
             }
         }
     }
+
+    pub fn replay_gain(&self) -> ReplayGainSettings {
+        ReplayGainSettings {
+            mode: self.replay_gain_mode,
+            preamp: self.replay_gain_preamp,
+            prevent_clipping: self.replay_gain_prevent_clipping,
+        }
+    }
+
+    /// Equalizer gains to apply, `None` when bypassed
+    pub fn equalizer(&self) -> Option<&EqualizerGains> {
+        self.equalizer_enabled.then_some(&self.equalizer_gains)
+    }
 }
 
 impl Default for Config {
@@ -67,6 +100,19 @@ impl Default for Config {
             library_paths: HashSet::new(),
             list_text_wrap: true,
             list_row_align_top: false,
+            watch_library: false,
+            scan_workers: 0,
+            resume_session: true,
+            replay_gain_mode: ReplayGainMode::Off,
+            replay_gain_preamp: 0,
+            replay_gain_prevent_clipping: true,
+            crossfade: 0,
+            equalizer_enabled: false,
+            equalizer_gains: EqualizerGains::default(),
+            equalizer_presets: Vec::new(),
+            output_device: None,
+            sleep_timer_fade: true,
+            fast_seek: false,
         }
     }
 }
@@ -75,9 +121,14 @@ impl Default for Config {
 #[serde(default)]
 pub struct State {
     pub muted: bool,
+    pub playback_rate: f64,
     pub playlist_nav_order: Vec<u32>,
     pub repeat: bool,
     pub repeat_mode: RepeatMode,
+    pub session: Option<SavedSession>,
+    /// Position in the saved session's current track in seconds, saved on its
+    /// own as it changes far more often than the session
+    pub session_position: f32,
     pub shuffle: bool,
     pub size_multiplier: f32,
     pub sort_by: SortBy,
@@ -91,9 +142,12 @@ impl Default for State {
     fn default() -> Self {
         Self {
             muted: false,
+            playback_rate: 1.0,
             playlist_nav_order: Vec::new(),
             repeat: false,
             repeat_mode: RepeatMode::All,
+            session: None,
+            session_position: 0.0,
             shuffle: false,
             size_multiplier: 8.0,
             sort_by: SortBy::Artist,
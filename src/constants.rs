@@ -16,18 +16,78 @@ pub const LIBRARY_FILENAME: &str = "library.json";
 pub const PLAYLISTS_DIR: &str = "playlists";
 pub const ARTWORK_DIR: &str = "artwork";
 pub const MIN_FILE_SIZE: u64 = 4096;
+pub const CONTENT_HASH_SAMPLE_BYTES: u64 = 64 * 1024;
+
+/// Playlist Repair Constants
+pub const MISSING_TRACK_DURATION_TOLERANCE_SECS: f32 = 2.0;
 
 /// Timing Constants
 pub const DOUBLE_CLICK_THRESHOLD_MS: u64 = 400;
 pub const TICK_INTERVAL_MS: u64 = 100;
 pub const PROGRESS_UPDATE_INTERVAL_MS: u64 = 200;
 pub const LIBRARY_UPDATE_INTERVAL_SECS: u64 = 10;
+pub const SESSION_SAVE_INTERVAL_SECS: u64 = 10;
 pub const GSTREAMER_TIMEOUT_SECS: u64 = 5;
+pub const WATCHER_DEBOUNCE_MS: u64 = 2000;
+pub const WATCHER_POLL_INTERVAL_MS: u64 = 250;
+pub const WATCHER_ROOT_CHECK_SECS: u64 = 5;
+
+/// Library Scan Constants
+pub const MAX_AUTO_SCAN_WORKERS: usize = 8;
+pub const SCAN_WORKER_CHOICES: &[usize] = &[0, 1, 2, 3, 4, 6, 8, 12, 16];
+
+/// ReplayGain Constants
+pub const REPLAY_GAIN_PREAMP_CHOICES: &[i32] = &[-12, -9, -6, -3, 0, 3, 6, 9, 12];
+
+/// Crossfade Constants
+pub const CROSSFADE_CHOICES: &[u32] = &[0, 1, 2, 3, 4, 5, 6, 8, 10, 12];
+
+/// Failed tracks skipped in a row before playback stops
+pub const MAX_PLAYBACK_ERROR_SKIPS: u32 = 5;
+
+/// Sleep Timer Constants
+pub const SLEEP_TIMER_MINUTES: &[u32] = &[15, 30, 45, 60, 90, 120];
+/// Length of the fade-out before the sleep timer stops playback
+pub const SLEEP_FADE_SECS: u64 = 30;
+
+/// Playback Speed Constants
+pub const MIN_PLAYBACK_RATE: f64 = 0.5;
+pub const MAX_PLAYBACK_RATE: f64 = 3.0;
+pub const PLAYBACK_RATE_CHOICES: &[f64] = &[0.5, 0.75, 1.0, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0];
+
+/// Equalizer Constants
+pub const EQUALIZER_BAND_FREQUENCIES: [u32; 10] =
+    [31, 62, 125, 250, 500, 1000, 2000, 4000, 8000, 16000];
+/// Largest boost or cut in dB
+pub const EQUALIZER_MAX_GAIN: i32 = 12;
+/// Gain changes are spread over ticks in steps of at most this many dB
+pub const EQUALIZER_RAMP_STEP: f64 = 1.5;
 
 /// Audio File Extensions
 pub const VALID_AUDIO_EXTENSIONS: &[&str] = &["flac", "m4a", "mp3", "ogg", "opus", "wav"];
 
+/// MIME types that can be opened, the same list as MimeType in the desktop entry
+pub const SUPPORTED_MIME_TYPES: &[&str] = &[
+    "audio/flac",
+    "audio/x-flac",
+    "audio/mpeg",
+    "audio/mp3",
+    "audio/ogg",
+    "audio/x-vorbis+ogg",
+    "audio/opus",
+    "audio/x-opus+ogg",
+    "audio/mp4",
+    "audio/x-m4a",
+    "audio/wav",
+    "audio/x-wav",
+    "audio/x-mpegurl",
+    "audio/mpegurl",
+    "audio/x-scpls",
+    "application/xspf+xml",
+];
+
 /// Widget IDs
 pub const NEW_PLAYLIST_INPUT_ID: &str = "new_playlist_input_id";
 pub const RENAME_PLAYLIST_INPUT_ID: &str = "rename_playlist_input_id";
+pub const EQUALIZER_PRESET_INPUT_ID: &str = "equalizer_preset_input_id";
 pub const SEARCH_INPUT_ID: &str = "Text Search";
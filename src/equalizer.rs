@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use crate::constants::{EQUALIZER_BAND_FREQUENCIES, EQUALIZER_MAX_GAIN};
+use crate::fl;
+use serde::{Deserialize, Serialize};
+
+/// Gains in dB, one per band of `EQUALIZER_BAND_FREQUENCIES`
+pub type EqualizerGains = [i32; EQUALIZER_BAND_FREQUENCIES.len()];
+
+/// A named set of band gains
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct EqualizerPreset {
+    pub name: String,
+    pub gains: EqualizerGains,
+}
+
+impl EqualizerPreset {
+    fn new(name: String, gains: EqualizerGains) -> Self {
+        Self {
+            name,
+            gains: gains.map(|gain| gain.clamp(-EQUALIZER_MAX_GAIN, EQUALIZER_MAX_GAIN)),
+        }
+    }
+}
+
+/// Presets that ship with the application
+pub fn builtin_presets() -> Vec<EqualizerPreset> {
+    vec![
+        EqualizerPreset::new(fl!("eq-flat"), [0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+        EqualizerPreset::new(fl!("eq-rock"), [5, 4, 3, 1, -1, -1, 1, 3, 4, 5]),
+        EqualizerPreset::new(fl!("eq-pop"), [-1, 1, 3, 4, 4, 2, 0, -1, -1, -1]),
+        EqualizerPreset::new(fl!("eq-jazz"), [3, 2, 1, 2, -1, -1, 0, 1, 2, 3]),
+        EqualizerPreset::new(fl!("eq-classical"), [4, 3, 2, 1, -1, -1, 0, 2, 3, 4]),
+        EqualizerPreset::new(fl!("eq-electronic"), [5, 4, 1, 0, -2, 2, 1, 1, 4, 5]),
+        EqualizerPreset::new(fl!("eq-bass-boost"), [7, 6, 5, 3, 1, 0, 0, 0, 0, 0]),
+        EqualizerPreset::new(fl!("eq-treble-boost"), [0, 0, 0, 0, 0, 1, 3, 5, 6, 7]),
+        EqualizerPreset::new(fl!("eq-vocal"), [-2, -3, -3, 1, 4, 4, 3, 1, 0, -2]),
+        EqualizerPreset::new(fl!("eq-loudness"), [5, 3, 0, 0, -1, 0, 0, 1, 3, 5]),
+    ]
+}
+
+/// Label for a band, e.g. "125 Hz" or "2 kHz"
+pub fn band_label(frequency: u32) -> String {
+    if frequency >= 1000 {
+        format!("{} kHz", frequency / 1000)
+    } else {
+        format!("{} Hz", frequency)
+    }
+}
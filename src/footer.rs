@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use crate::app::{AppModel, Message};
+use crate::constants::PLAYBACK_RATE_CHOICES;
 use crate::fl;
 use crate::helpers::*;
 use crate::library::MediaMetaData;
@@ -9,15 +10,16 @@ use cosmic::widget::tooltip::Position;
 use cosmic::{
     Element, cosmic_theme,
     iced::{
-        Alignment, Font, Length,
+        Alignment, Border, Color, Font, Length,
         font::{self, Weight},
+        widget::{Stack, container},
     },
     theme, widget,
     widget::image::Handle,
 };
 use std::sync::Arc;
 
-pub fn footer<'a>(app: &AppModel) -> Element<'a, Message> {
+pub fn footer<'a>(app: &'a AppModel) -> Element<'a, Message> {
     let cosmic_theme::Spacing {
         space_xxs,
         space_xs,
@@ -46,11 +48,13 @@ pub fn footer<'a>(app: &AppModel) -> Element<'a, Message> {
             ))
             .push(
                 widget::row()
-                    .push(widget::text(if app.update_progress == 0.0 {
-                        fl!("scanning-paths")
-                    } else {
-                        app.update_progress_display.to_string()
-                    }))
+                    .push(widget::text(
+                        if app.update_progress == 0.0 && !app.is_analyzing {
+                            fl!("scanning-paths")
+                        } else {
+                            app.update_progress_display.to_string()
+                        },
+                    ))
                     .push(widget::horizontal_space())
                     .push(widget::tooltip(
                         widget::button::icon(widget::icon::from_name("process-stop-symbolic"))
@@ -126,6 +130,60 @@ pub fn footer<'a>(app: &AppModel) -> Element<'a, Message> {
             .push(now_playing_text),
     );
 
+    // The A-B button sets A, then B, then clears the loop
+    let (loop_start, loop_end) = app.playback_service.ab_loop();
+    let (loop_message, loop_tooltip) = match (loop_start, loop_end) {
+        (None, _) => (Message::SetLoopStart, fl!("set-loop-start")),
+        (Some(_), None) => (Message::SetLoopEnd, fl!("set-loop-end")),
+        (Some(_), Some(_)) => (Message::ClearLoop, fl!("clear-loop")),
+    };
+    let loop_button = if loop_end.is_some() {
+        widget::button::suggested("A-B")
+    } else {
+        widget::button::text("A-B")
+    };
+
+    let duration = now_playing.duration.unwrap_or(0.0);
+    let slider = widget::slider(
+        0.0..=duration,
+        app.playback_service.progress(),
+        Message::SliderSeek,
+    )
+    .on_release(Message::ReleaseSlider);
+
+    // Draw the loop over the slider, up to the position while B isn't set
+    let slider: Element<Message> = match loop_start.filter(|_| duration > 0.0) {
+        Some(start) => {
+            let end = loop_end
+                .unwrap_or(app.playback_service.progress())
+                .max(start);
+            // Parts of the track before, in and after the loop, out of 1000
+            let portion = |seconds: f32| (seconds.clamp(0.0, duration) / duration * 1000.0) as u16;
+            let (before, looped) = (portion(start), portion(end) - portion(start));
+            let after = 1000 - portion(end);
+
+            // A zero portion would fill the row
+            let region = widget::row()
+                .push(widget::horizontal_space().width(Length::FillPortion(before.max(1))))
+                .push(
+                    widget::container(widget::vertical_space().height(Length::Fixed(8.0)))
+                        .width(Length::FillPortion(looped.max(1)))
+                        .class(theme::Container::custom(loop_region_style)),
+                )
+                .push(widget::horizontal_space().width(Length::FillPortion(after.max(1))));
+
+            Stack::with_children(vec![
+                slider.into(),
+                widget::container(region)
+                    .height(Length::Fill)
+                    .align_y(Alignment::Center)
+                    .into(),
+            ])
+            .into()
+        }
+        None => slider.into(),
+    };
+
     let play_icon = match app.playback_service.status() {
         PlaybackStatus::Stopped => "media-playback-start-symbolic",
         PlaybackStatus::Paused => "media-playback-start-symbolic",
@@ -142,17 +200,10 @@ pub fn footer<'a>(app: &AppModel) -> Element<'a, Message> {
                 .spacing(space_xxs)
                 .width(Length::Fill)
                 .push(widget::text(format_time(app.playback_service.progress())))
-                .push(
-                    widget::slider(
-                        0.0..=now_playing.duration.unwrap_or(0.0),
-                        app.playback_service.progress(),
-                        Message::SliderSeek,
-                    )
-                    .on_release(Message::ReleaseSlider),
-                )
+                .push(slider)
                 .push(widget::text(format_time_left(
                     app.playback_service.progress(),
-                    now_playing.duration.unwrap_or(0.0),
+                    duration,
                 ))),
         )
         // Spacer above controls
@@ -188,6 +239,16 @@ pub fn footer<'a>(app: &AppModel) -> Element<'a, Message> {
                     widget::text(fl!("next")),
                     Position::Bottom,
                 ))
+                .push(widget::tooltip(
+                    loop_button.on_press_maybe(
+                        app.playback_service
+                            .now_playing()
+                            .is_some()
+                            .then_some(loop_message),
+                    ),
+                    widget::text(loop_tooltip),
+                    Position::Bottom,
+                ))
                 .push(widget::horizontal_space().width(Length::Fill)),
         );
 
@@ -208,6 +269,21 @@ pub fn footer<'a>(app: &AppModel) -> Element<'a, Message> {
             .align_y(Alignment::Center)
             .spacing(space_xxs)
             .push(widget::horizontal_space().width(Length::FillPortion(1)))
+            .push(widget::tooltip(
+                widget::dropdown(
+                    &app.playback_rate_labels,
+                    PLAYBACK_RATE_CHOICES
+                        .iter()
+                        .position(|rate| *rate == app.playback_service.rate()),
+                    |index| {
+                        Message::SetPlaybackRate(
+                            PLAYBACK_RATE_CHOICES.get(index).copied().unwrap_or(1.0),
+                        )
+                    },
+                ),
+                widget::text(fl!("playback-speed")),
+                Position::Bottom,
+            ))
             .push(
                 widget::button::icon(widget::icon::from_name(volume_icon))
                     .on_press(Message::ToggleMute),
@@ -230,3 +306,19 @@ pub fn footer<'a>(app: &AppModel) -> Element<'a, Message> {
         .layer(cosmic_theme::Layer::Primary)
         .into()
 }
+
+/// Translucent accent over the looped part of the slider
+fn loop_region_style(theme: &theme::Theme) -> container::Style {
+    let cosmic = theme.cosmic();
+    let mut color = Color::from(cosmic.accent_color());
+    color.a = 0.35;
+
+    container::Style {
+        background: Some(color.into()),
+        border: Border {
+            radius: cosmic.radius_xs().into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
@@ -3,7 +3,7 @@
 use cosmic::widget::image::Handle;
 use std::collections::{HashMap, VecDeque};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
@@ -78,6 +78,10 @@ impl ImageStore {
 }
 
 impl ImageStore {
+    pub fn artwork_dir(&self) -> &Path {
+        &self.artwork_dir
+    }
+
     pub fn request(&self, path: String) {
         let artwork_path = self.artwork_dir.join(path);
 
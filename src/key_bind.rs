@@ -24,6 +24,11 @@ pub fn key_binds() -> HashMap<KeyBind, MenuAction> {
     }
 
     bind!([Ctrl], Key::Character("u".into()), UpdateLibrary);
+    bind!(
+        [Ctrl, Shift],
+        Key::Character("U".into()),
+        PartialUpdateLibrary
+    );
     bind!([Ctrl], Key::Character("q".into()), Quit);
     bind!([Ctrl], Key::Character(",".into()), Settings);
     bind!([Ctrl], Key::Character("-".into()), ZoomOut);
@@ -33,6 +38,9 @@ pub fn key_binds() -> HashMap<KeyBind, MenuAction> {
     bind!([], Key::Named(Named::F2), RenamePlaylist);
     bind!([Ctrl], Key::Named(Named::ArrowUp), MoveNavUp);
     bind!([Ctrl], Key::Named(Named::ArrowDown), MoveNavDown);
+    bind!([Ctrl], Key::Character("[".into()), SetLoopStart);
+    bind!([Ctrl], Key::Character("]".into()), SetLoopEnd);
+    bind!([Ctrl], Key::Character("\\".into()), ClearLoop);
 
     key_binds
 }
@@ -40,7 +40,10 @@ impl Library {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MediaMetaData {
+    /// Unique per file, kept when the file is moved or renamed
     pub id: Option<String>,
+    /// Hash of the audio payload, shared by identical copies
+    pub content_hash: Option<String>,
     pub title: Option<String>,
     pub artist: Option<String>,
     pub album: Option<String>,
@@ -52,12 +55,22 @@ pub struct MediaMetaData {
     pub track_count: Option<u32>,
     pub duration: Option<f32>,
     pub artwork_filename: Option<String>,
+    pub file_modified: Option<u64>,
+    pub file_size: Option<u64>,
+    /// Unix time the file was first added to the library
+    pub date_added: Option<u64>,
+    /// ReplayGain adjustments in dB and sample peaks, 1.0 being full scale
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
 }
 
 impl MediaMetaData {
     pub fn new() -> Self {
         Self {
             id: None,
+            content_hash: None,
             title: None,
             artist: None,
             album: None,
@@ -69,6 +82,18 @@ impl MediaMetaData {
             track_count: None,
             duration: None,
             artwork_filename: None,
+            file_modified: None,
+            file_size: None,
+            date_added: None,
+            track_gain: None,
+            track_peak: None,
+            album_gain: None,
+            album_peak: None,
         }
     }
+
+    /// Check whether the stored file stamp matches the file on disk
+    pub fn is_unchanged(&self, modified: u64, size: u64) -> bool {
+        self.file_modified == Some(modified) && self.file_size == Some(size)
+    }
 }
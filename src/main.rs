@@ -3,6 +3,7 @@
 mod app;
 mod config;
 mod constants;
+mod equalizer;
 mod footer;
 mod helpers;
 mod i18n;
@@ -15,7 +16,10 @@ mod page;
 mod playback_state;
 mod player;
 mod playlist;
+mod remote;
 mod services;
+mod smart_playlist;
+mod smart_playlist_dialog;
 
 use app::Flags;
 use config::{Config, State};
@@ -23,14 +27,38 @@ use cosmic::{
     app::Settings,
     iced::{Limits, Size},
 };
+use std::ffi::OsString;
+use std::path::PathBuf;
 
 fn main() -> cosmic::iced::Result {
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+
+    // Options control the running instance instead of starting one
+    if args
+        .first()
+        .is_some_and(|arg| arg.to_string_lossy().starts_with("--"))
+    {
+        std::process::exit(remote::run(&args));
+    }
+
     // Get the system's preferred languages.
     let requested_languages = i18n_embed::DesktopLanguageRequester::requested_languages();
 
     // Enable localizations to be applied.
     i18n::init(&requested_languages);
 
+    // Files, folders and playlist files to play right away
+    let open_paths: Vec<PathBuf> = args
+        .into_iter()
+        .map(|arg| std::path::absolute(&arg).unwrap_or_else(|_| PathBuf::from(arg)))
+        .collect();
+
+    // Hand them over to a running instance rather than starting another one
+    #[cfg(feature = "single-instance")]
+    if remote::forward_open(&open_paths) {
+        return Ok(());
+    }
+
     let (config_handler, config) = Config::load();
     let (state_handler, state) = State::load();
 
@@ -44,6 +72,7 @@ fn main() -> cosmic::iced::Result {
         config_handler,
         state_handler,
         state,
+        open_paths,
     };
 
     // Starts the application's event loop with `()` as the application's flags.
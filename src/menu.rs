@@ -1,8 +1,9 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use crate::app::{AppModel, MenuAction, Message};
+use crate::constants::SLEEP_TIMER_MINUTES;
 use crate::fl;
-use crate::playback_state::RepeatMode;
+use crate::playback_state::{RepeatMode, SleepTimer};
 use cosmic::{Apply, Element, iced::Length, widget::menu};
 
 pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
@@ -56,6 +57,8 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
         }
     };
 
+    let has_missing = !selected_playlist.is_library() && selected_playlist.missing_count() > 0;
+
     let mut selected_playlist_list = Vec::new();
     let mut now_playing_playlist_list = Vec::new();
 
@@ -71,6 +74,11 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
     // Add ordered playlists
     app.state.playlist_nav_order.iter().for_each(|p| {
         if let Ok(playlist) = app.playlist_service.get(*p) {
+            // Smart playlist contents come from their rules
+            if playlist.is_smart() {
+                return;
+            }
+
             selected_playlist_list.push(menu::Item::Button(
                 playlist.name().to_string(),
                 None,
@@ -88,7 +96,7 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
     // Add unordered playlists
     app.playlist_service
         .user_playlists()
-        .filter(|p| !app.state.playlist_nav_order.contains(&p.id()))
+        .filter(|p| !p.is_smart() && !app.state.playlist_nav_order.contains(&p.id()))
         .for_each(|p| {
             selected_playlist_list.push(menu::Item::Button(
                 p.name().to_string(),
@@ -129,6 +137,70 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
                     } else {
                         menu::Item::Button(fl!("update-library"), None, MenuAction::UpdateLibrary)
                     },
+                    if app.is_updating {
+                        menu::Item::ButtonDisabled(
+                            fl!("partial-update-library"),
+                            None,
+                            MenuAction::PartialUpdateLibrary,
+                        )
+                    } else {
+                        menu::Item::Button(
+                            fl!("partial-update-library"),
+                            None,
+                            MenuAction::PartialUpdateLibrary,
+                        )
+                    },
+                    menu::Item::Divider,
+                    if selected_count > 0 && !app.is_updating {
+                        menu::Item::Button(
+                            fl!("analyze-loudness"),
+                            None,
+                            MenuAction::AnalyzeLoudness,
+                        )
+                    } else {
+                        menu::Item::ButtonDisabled(
+                            fl!("analyze-loudness"),
+                            None,
+                            MenuAction::AnalyzeLoudness,
+                        )
+                    },
+                    if selected_count > 0 && !app.is_updating {
+                        menu::Item::Button(
+                            fl!("analyze-album-loudness"),
+                            None,
+                            MenuAction::AnalyzeAlbumLoudness,
+                        )
+                    } else {
+                        menu::Item::ButtonDisabled(
+                            fl!("analyze-album-loudness"),
+                            None,
+                            MenuAction::AnalyzeAlbumLoudness,
+                        )
+                    },
+                    if selected_count > 0 {
+                        menu::Item::Button(
+                            fl!("write-loudness-tags-menu"),
+                            None,
+                            MenuAction::WriteLoudnessTags,
+                        )
+                    } else {
+                        menu::Item::ButtonDisabled(
+                            fl!("write-loudness-tags-menu"),
+                            None,
+                            MenuAction::WriteLoudnessTags,
+                        )
+                    },
+                    menu::Item::Divider,
+                    menu::Item::Button(
+                        fl!("import-playlist-menu"),
+                        None,
+                        MenuAction::ImportPlaylist,
+                    ),
+                    menu::Item::Button(
+                        fl!("export-playlist-menu"),
+                        None,
+                        MenuAction::ExportPlaylist,
+                    ),
                     menu::Item::Divider,
                     menu::Item::Button(fl!("quit"), None, MenuAction::Quit),
                 ],
@@ -140,6 +212,24 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
                 &app.key_binds,
                 vec![
                     menu::Item::Button(fl!("new-playlist-menu"), None, MenuAction::NewPlaylist),
+                    menu::Item::Button(
+                        fl!("new-smart-playlist-menu"),
+                        None,
+                        MenuAction::NewSmartPlaylist,
+                    ),
+                    if selected_playlist.is_smart() {
+                        menu::Item::Button(
+                            fl!("edit-smart-playlist-menu"),
+                            None,
+                            MenuAction::EditSmartPlaylist,
+                        )
+                    } else {
+                        menu::Item::ButtonDisabled(
+                            fl!("edit-smart-playlist-menu"),
+                            None,
+                            MenuAction::EditSmartPlaylist,
+                        )
+                    },
                     if !selected_playlist.is_library() {
                         menu::Item::Button(
                             fl!("rename-playlist-menu"),
@@ -168,7 +258,10 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
                     },
                     menu::Item::Divider,
                     menu::Item::Folder(fl!("add-selected-to"), selected_playlist_list),
-                    if has_playlist && !selected_playlist.is_library() {
+                    if has_playlist
+                        && !selected_playlist.is_library()
+                        && !selected_playlist.is_smart()
+                    {
                         menu::Item::Button(
                             fl!("remove-selected"),
                             None,
@@ -181,6 +274,32 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
                             MenuAction::RemoveSelectedFromPlaylist,
                         )
                     },
+                    if has_missing {
+                        menu::Item::Button(
+                            fl!("locate-missing-tracks"),
+                            None,
+                            MenuAction::LocateMissingTracks,
+                        )
+                    } else {
+                        menu::Item::ButtonDisabled(
+                            fl!("locate-missing-tracks"),
+                            None,
+                            MenuAction::LocateMissingTracks,
+                        )
+                    },
+                    if has_missing {
+                        menu::Item::Button(
+                            fl!("remove-missing-tracks-menu"),
+                            None,
+                            MenuAction::RemoveMissingTracks,
+                        )
+                    } else {
+                        menu::Item::ButtonDisabled(
+                            fl!("remove-missing-tracks-menu"),
+                            None,
+                            MenuAction::RemoveMissingTracks,
+                        )
+                    },
                     menu::Item::Divider,
                     menu::Item::Folder(fl!("add-now-playing-to"), now_playing_playlist_list),
                     menu::Item::Divider,
@@ -204,6 +323,23 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
             menu::items(
                 &app.key_binds,
                 vec![
+                    if selected_count > 0 {
+                        menu::Item::Button(fl!("play-next"), None, MenuAction::PlayNext)
+                    } else {
+                        menu::Item::ButtonDisabled(fl!("play-next"), None, MenuAction::PlayNext)
+                    },
+                    if selected_count > 0 {
+                        menu::Item::Button(fl!("add-to-queue"), None, MenuAction::AddToQueue)
+                    } else {
+                        menu::Item::ButtonDisabled(
+                            fl!("add-to-queue"),
+                            None,
+                            MenuAction::AddToQueue,
+                        )
+                    },
+                    menu::Item::Button(fl!("queue-menu"), None, MenuAction::QueuePanel),
+                    menu::Item::Button(fl!("equalizer-menu"), None, MenuAction::EqualizerPanel),
+                    menu::Item::Divider,
                     menu::Item::CheckBox(
                         fl!("shuffle"),
                         None,
@@ -229,6 +365,15 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
                         repeat_all,
                         MenuAction::ToggleRepeatMode,
                     ),
+                    menu::Item::Divider,
+                    menu::Item::CheckBox(
+                        fl!("stop-after-current"),
+                        None,
+                        app.playback_service.stop_after_current(),
+                        MenuAction::ToggleStopAfterCurrent,
+                    ),
+                    menu::Item::Folder(fl!("sleep-timer"), sleep_timer_items(app)),
+                    menu::Item::Folder(fl!("ab-loop"), ab_loop_items(app)),
                 ],
             ),
         ),
@@ -253,3 +398,71 @@ pub fn menu_bar<'a>(app: &AppModel) -> Element<'a, Message> {
     .width(Length::Fill)
     .into()
 }
+
+/// Setting and clearing the A-B loop of the current track
+fn ab_loop_items(app: &AppModel) -> Vec<menu::Item<MenuAction, String>> {
+    let playing = app.playback_service.now_playing().is_some();
+    let (start, end) = app.playback_service.ab_loop();
+
+    let item = |enabled: bool, label: String, action: MenuAction| {
+        if enabled {
+            menu::Item::Button(label, None, action)
+        } else {
+            menu::Item::ButtonDisabled(label, None, action)
+        }
+    };
+
+    vec![
+        item(playing, fl!("set-loop-start"), MenuAction::SetLoopStart),
+        item(playing, fl!("set-loop-end"), MenuAction::SetLoopEnd),
+        item(
+            start.is_some() || end.is_some(),
+            fl!("clear-loop"),
+            MenuAction::ClearLoop,
+        ),
+    ]
+}
+
+/// Sleep timer choices, the active one is checked
+fn sleep_timer_items(app: &AppModel) -> Vec<menu::Item<MenuAction, String>> {
+    let active = app.playback_service.sleep_timer();
+
+    let mut items = vec![menu::Item::CheckBox(
+        fl!("sleep-timer-off"),
+        None,
+        active.is_none(),
+        MenuAction::SleepTimer(None),
+    )];
+
+    let timers = SLEEP_TIMER_MINUTES
+        .iter()
+        .map(|minutes| {
+            (
+                fl!("sleep-timer-minutes", minutes = minutes),
+                SleepTimer::Minutes(*minutes),
+            )
+        })
+        .chain([
+            (fl!("end-of-track"), SleepTimer::EndOfTrack),
+            (fl!("end-of-album"), SleepTimer::EndOfAlbum),
+        ]);
+
+    for (label, timer) in timers {
+        items.push(menu::Item::CheckBox(
+            label,
+            None,
+            active == Some(timer),
+            MenuAction::SleepTimer(Some(timer)),
+        ));
+    }
+
+    items.push(menu::Item::Divider);
+    items.push(menu::Item::CheckBox(
+        fl!("sleep-fade-out"),
+        None,
+        app.config.sleep_timer_fade,
+        MenuAction::ToggleSleepFade,
+    ));
+
+    items
+}
@@ -1,18 +1,70 @@
 // SPDX-License-Identifier: GPL-3.0
+use crate::constants::{MAX_PLAYBACK_RATE, MIN_PLAYBACK_RATE, SUPPORTED_MIME_TYPES};
 use crate::playback_state::PlaybackStatus;
+use crate::playlist::Track;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::UnboundedSender;
+use url::Url;
 use zbus::interface;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, Value};
+
+/// Track ids are this prefix followed by the track's `entry_id`
+const TRACK_ID_PREFIX: &str = "/com/galacticpirateradio/etherealwaves/track/";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+/// Playlist ids are this prefix followed by the playlist id
+const PLAYLIST_ID_PREFIX: &str = "/com/galacticpirateradio/etherealwaves/playlist/";
+
+pub type Metadata = HashMap<String, Value<'static>>;
 
 #[derive(Clone, Default)]
 pub struct MprisState {
     pub playback_status: PlaybackStatus,
-    pub metadata: HashMap<String, zbus::zvariant::Value<'static>>,
+    pub metadata: Metadata,
     pub position: i64,
     pub volume: f64,
+    pub rate: f64,
     pub shuffle: bool,
     pub loop_status: String, // "None", "Track", "Playlist"
+    pub capabilities: Capabilities,
+    /// Entry ids of the session tracks in play order
+    pub tracks: Vec<u32>,
+    pub track_metadata: HashMap<u32, Metadata>,
+    /// Playlists in navigation order
+    pub playlists: Vec<MprisPlaylist>,
+    pub active_playlist: Option<u32>,
+}
+
+/// What the player controls can do right now
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    pub can_play: bool,
+    pub can_pause: bool,
+    pub can_seek: bool,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+}
+
+/// A playlist as listed by the Playlists interface
+#[derive(Clone, Debug, PartialEq)]
+pub struct MprisPlaylist {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Id, name and icon as MPRIS expects a playlist
+type PlaylistStruct = (ObjectPath<'static>, String, String);
+
+impl MprisPlaylist {
+    pub fn to_struct(&self) -> PlaylistStruct {
+        (
+            ObjectPath::from_string_unchecked(format!("{}{}", PLAYLIST_ID_PREFIX, self.id)),
+            self.name.clone(),
+            String::new(),
+        )
+    }
 }
 
 impl Default for PlaybackStatus {
@@ -56,11 +108,18 @@ impl MediaPlayer2Player {
         let _ = self.tx.send(MprisCommand::Seek(offset));
     }
 
-    fn set_position(&self, _track_id: zbus::zvariant::ObjectPath<'_>, position: i64) {
-        let _ = self.tx.send(MprisCommand::SetPosition(position));
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        if let Some(id) = entry_id(&track_id) {
+            let _ = self.tx.send(MprisCommand::SetPosition(id, position));
+        }
     }
 
-    fn open_uri(&self, _uri: String) {}
+    fn open_uri(&self, uri: String) {
+        let _ = self.tx.send(MprisCommand::OpenUri(uri));
+    }
+
+    #[zbus(signal)]
+    pub async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
 
     // Properties
     #[zbus(property)]
@@ -94,7 +153,7 @@ impl MediaPlayer2Player {
     }
 
     #[zbus(property)]
-    fn metadata(&self) -> HashMap<String, zbus::zvariant::Value<'static>> {
+    fn metadata(&self) -> Metadata {
         self.state.lock().unwrap().metadata.clone()
     }
 
@@ -115,35 +174,37 @@ impl MediaPlayer2Player {
 
     #[zbus(property)]
     fn minimum_rate(&self) -> f64 {
-        1.0
+        MIN_PLAYBACK_RATE
     }
 
     #[zbus(property)]
     fn maximum_rate(&self) -> f64 {
-        1.0
+        MAX_PLAYBACK_RATE
     }
 
     #[zbus(property)]
     fn rate(&self) -> f64 {
-        1.0
+        self.state.lock().unwrap().rate
     }
 
     #[zbus(property)]
-    fn set_rate(&self, _rate: f64) {}
+    fn set_rate(&self, rate: f64) {
+        let _ = self.tx.send(MprisCommand::SetRate(rate));
+    }
 
     #[zbus(property)]
     fn can_play(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_play
     }
 
     #[zbus(property)]
     fn can_pause(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_pause
     }
 
     #[zbus(property)]
     fn can_seek(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_seek
     }
 
     #[zbus(property)]
@@ -153,36 +214,42 @@ impl MediaPlayer2Player {
 
     #[zbus(property)]
     fn can_go_next(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_go_next
     }
 
     #[zbus(property)]
     fn can_go_previous(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_go_previous
     }
 }
 
-pub struct MediaPlayer2;
+pub struct MediaPlayer2 {
+    pub tx: UnboundedSender<MprisCommand>,
+}
 
 #[interface(name = "org.mpris.MediaPlayer2")]
 impl MediaPlayer2 {
-    fn raise(&self) {}
+    fn raise(&self) {
+        let _ = self.tx.send(MprisCommand::Raise);
+    }
 
-    fn quit(&self) {}
+    fn quit(&self) {
+        let _ = self.tx.send(MprisCommand::Quit);
+    }
 
     #[zbus(property)]
     fn can_quit(&self) -> bool {
-        false
+        true
     }
 
     #[zbus(property)]
     fn can_raise(&self) -> bool {
-        false
+        true
     }
 
     #[zbus(property)]
     fn has_track_list(&self) -> bool {
-        false
+        true
     }
 
     #[zbus(property)]
@@ -202,17 +269,292 @@ impl MediaPlayer2 {
 
     #[zbus(property)]
     fn supported_mime_types(&self) -> Vec<&str> {
-        vec![
-            "audio/mpeg",
-            "audio/mp4",
-            "audio/ogg",
-            "audio/opus",
-            "audio/flac",
-            "audio/wav",
-        ]
+        SUPPORTED_MIME_TYPES.to_vec()
     }
 }
 
+pub struct MediaPlayer2TrackList {
+    pub tx: UnboundedSender<MprisCommand>,
+    pub state: Arc<Mutex<MprisState>>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.TrackList")]
+impl MediaPlayer2TrackList {
+    fn get_tracks_metadata(&self, track_ids: Vec<ObjectPath<'_>>) -> Vec<Metadata> {
+        let state = self.state.lock().unwrap();
+        track_ids
+            .iter()
+            .filter_map(entry_id)
+            .filter_map(|id| state.track_metadata.get(&id).cloned())
+            .collect()
+    }
+
+    fn add_track(&self, uri: String, after_track: ObjectPath<'_>, set_as_current: bool) {
+        let _ = self.tx.send(MprisCommand::AddTrack {
+            uri,
+            after: entry_id(&after_track),
+            set_as_current,
+        });
+    }
+
+    fn remove_track(&self, track_id: ObjectPath<'_>) {
+        if let Some(id) = entry_id(&track_id) {
+            let _ = self.tx.send(MprisCommand::RemoveTrack(id));
+        }
+    }
+
+    fn go_to(&self, track_id: ObjectPath<'_>) {
+        if let Some(id) = entry_id(&track_id) {
+            let _ = self.tx.send(MprisCommand::GoTo(id));
+        }
+    }
+
+    #[zbus(property(emits_changed_signal = "invalidates"))]
+    fn tracks(&self) -> Vec<ObjectPath<'static>> {
+        self.state
+            .lock()
+            .unwrap()
+            .tracks
+            .iter()
+            .map(|id| track_id(*id))
+            .collect()
+    }
+
+    #[zbus(property)]
+    fn can_edit_tracks(&self) -> bool {
+        true
+    }
+
+    #[zbus(signal)]
+    pub async fn track_list_replaced(
+        emitter: &SignalEmitter<'_>,
+        tracks: Vec<ObjectPath<'_>>,
+        current_track: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn track_added(
+        emitter: &SignalEmitter<'_>,
+        metadata: Metadata,
+        after_track: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn track_removed(
+        emitter: &SignalEmitter<'_>,
+        track_id: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+}
+
+pub struct MediaPlayer2Playlists {
+    pub tx: UnboundedSender<MprisCommand>,
+    pub state: Arc<Mutex<MprisState>>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Playlists")]
+impl MediaPlayer2Playlists {
+    fn activate_playlist(&self, playlist_id: ObjectPath<'_>) {
+        let id = playlist_id
+            .as_str()
+            .strip_prefix(PLAYLIST_ID_PREFIX)
+            .and_then(|id| id.parse().ok());
+
+        if let Some(id) = id {
+            let _ = self.tx.send(MprisCommand::ActivatePlaylist(id));
+        }
+    }
+
+    fn get_playlists(
+        &self,
+        index: u32,
+        max_count: u32,
+        order: String,
+        reverse_order: bool,
+    ) -> Vec<PlaylistStruct> {
+        // Navigation order is the user defined one
+        let mut playlists = self.state.lock().unwrap().playlists.clone();
+        if order == "Alphabetical" {
+            playlists.sort_by_key(|p| p.name.to_lowercase());
+        }
+        if reverse_order {
+            playlists.reverse();
+        }
+
+        playlists
+            .iter()
+            .skip(index as usize)
+            .take(max_count as usize)
+            .map(MprisPlaylist::to_struct)
+            .collect()
+    }
+
+    #[zbus(property)]
+    fn playlist_count(&self) -> u32 {
+        self.state.lock().unwrap().playlists.len() as u32
+    }
+
+    #[zbus(property)]
+    fn orderings(&self) -> Vec<&str> {
+        vec!["Alphabetical", "UserDefined"]
+    }
+
+    #[zbus(property)]
+    fn active_playlist(&self) -> (bool, PlaylistStruct) {
+        let state = self.state.lock().unwrap();
+        let active = state
+            .active_playlist
+            .and_then(|id| state.playlists.iter().find(|p| p.id == id));
+
+        match active {
+            Some(playlist) => (true, playlist.to_struct()),
+            None => (
+                false,
+                (
+                    ObjectPath::from_static_str_unchecked("/"),
+                    String::new(),
+                    String::new(),
+                ),
+            ),
+        }
+    }
+
+    #[zbus(signal)]
+    pub async fn playlist_changed(
+        emitter: &SignalEmitter<'_>,
+        playlist: PlaylistStruct,
+    ) -> zbus::Result<()>;
+}
+
+/// How the track list changed since the last update
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackListChange {
+    /// A track was inserted after the given one, `None` at the start
+    Added(u32, Option<u32>),
+    Removed(u32),
+    Replaced,
+}
+
+impl TrackListChange {
+    /// Work out the change between two lists of entry ids
+    ///
+    /// A single insertion or removal is reported as such, anything else
+    /// replaces the whole list.
+    pub fn between(old: &[u32], new: &[u32]) -> Self {
+        // Position of the single extra entry in `longer`
+        let extra = |longer: &[u32], shorter: &[u32]| {
+            if longer.len() != shorter.len() + 1 {
+                return None;
+            }
+            let index = longer
+                .iter()
+                .zip(shorter)
+                .position(|(a, b)| a != b)
+                .unwrap_or(shorter.len());
+            (longer[index + 1..] == shorter[index..]).then_some(index)
+        };
+
+        if let Some(index) = extra(new, old) {
+            let after = index.checked_sub(1).map(|i| new[i]);
+            TrackListChange::Added(new[index], after)
+        } else if let Some(index) = extra(old, new) {
+            TrackListChange::Removed(old[index])
+        } else {
+            TrackListChange::Replaced
+        }
+    }
+}
+
+/// Object path identifying a track
+pub fn track_id(entry_id: u32) -> ObjectPath<'static> {
+    ObjectPath::from_string_unchecked(format!("{}{}", TRACK_ID_PREFIX, entry_id))
+}
+
+/// Object path for `None`, e.g. when nothing is playing
+pub fn track_id_or_none(entry_id: Option<u32>) -> ObjectPath<'static> {
+    entry_id.map_or_else(|| ObjectPath::from_static_str_unchecked(NO_TRACK), track_id)
+}
+
+/// Entry id of a track from its object path
+fn entry_id(track_id: &ObjectPath<'_>) -> Option<u32> {
+    track_id
+        .as_str()
+        .strip_prefix(TRACK_ID_PREFIX)?
+        .parse()
+        .ok()
+}
+
+/// Metadata of a track as MPRIS expects it, with every field that is known
+pub fn track_metadata(track: &Track, artwork_dir: &Path) -> Metadata {
+    let metadata = &track.metadata;
+    let mut meta = HashMap::new();
+
+    meta.insert(
+        "mpris:trackid".to_string(),
+        Value::new(track_id(track.entry_id)),
+    );
+
+    // Duration (in microseconds)
+    if let Some(duration) = metadata.duration {
+        meta.insert(
+            "mpris:length".to_string(),
+            Value::new((duration * 1_000_000.0) as i64),
+        );
+    }
+
+    // Artwork is cached under its own file name
+    let art_url = metadata
+        .artwork_filename
+        .as_ref()
+        .and_then(|filename| Url::from_file_path(artwork_dir.join(filename)).ok());
+    if let Some(art_url) = art_url {
+        meta.insert("mpris:artUrl".to_string(), Value::new(art_url.to_string()));
+    }
+
+    if let Ok(url) = Url::from_file_path(&track.path) {
+        meta.insert("xesam:url".to_string(), Value::new(url.to_string()));
+    }
+
+    if let Some(title) = &metadata.title {
+        meta.insert("xesam:title".to_string(), Value::new(title.clone()));
+    }
+
+    // Artists and genres are lists
+    if let Some(artist) = &metadata.artist {
+        meta.insert("xesam:artist".to_string(), Value::new(vec![artist.clone()]));
+    }
+
+    if let Some(album_artist) = &metadata.album_artist {
+        meta.insert(
+            "xesam:albumArtist".to_string(),
+            Value::new(vec![album_artist.clone()]),
+        );
+    }
+
+    if let Some(album) = &metadata.album {
+        meta.insert("xesam:album".to_string(), Value::new(album.clone()));
+    }
+
+    if let Some(genre) = &metadata.genre {
+        meta.insert("xesam:genre".to_string(), Value::new(vec![genre.clone()]));
+    }
+
+    if let Some(track_number) = metadata.track_number {
+        meta.insert(
+            "xesam:trackNumber".to_string(),
+            Value::new(track_number as i32),
+        );
+    }
+
+    if let Some(disc_number) = metadata.album_disc_number {
+        meta.insert(
+            "xesam:discNumber".to_string(),
+            Value::new(disc_number as i32),
+        );
+    }
+
+    meta
+}
+
 #[derive(Debug, Clone)]
 pub enum MprisCommand {
     Play,
@@ -222,8 +564,25 @@ pub enum MprisCommand {
     Previous,
     Stop,
     Seek(i64),
-    SetPosition(i64),
+    /// Entry id of the track and the position in microseconds
+    SetPosition(u32, i64),
+    OpenUri(String),
     SetVolume(f64),
+    SetRate(f64),
     SetLoopStatus(String),
     SetShuffle(bool),
+    AddTrack {
+        uri: String,
+        after: Option<u32>,
+        set_as_current: bool,
+    },
+    RemoveTrack(u32),
+    GoTo(u32),
+    ActivatePlaylist(u32),
+    /// Files, folders and playlist files handed over by another invocation
+    Open(Vec<PathBuf>),
+    /// Files, folders and playlist files to add to the queue
+    Enqueue(Vec<PathBuf>),
+    Raise,
+    Quit,
 }
@@ -3,6 +3,7 @@
 use crate::app::{AppModel, Message, SortBy};
 use crate::constants::*;
 use crate::fl;
+use cosmic::widget::tooltip::Position;
 use cosmic::{
     cosmic_theme,
     iced::{Alignment, Color, Length},
@@ -92,6 +93,19 @@ pub fn content<'a>(app: &AppModel) -> widget::Column<'a, Message> {
                 .align_y(Alignment::Center)
                 .height(view_model.row_height),
             );
+        } else if track.1.missing {
+            // File is gone or no longer in the library
+            row_element = row_element.push(
+                widget::container(widget::tooltip(
+                    widget::icon::from_name("dialog-warning-symbolic").size(16),
+                    widget::text(fl!("missing-file")),
+                    Position::Bottom,
+                ))
+                .width(Length::Fixed(view_model.icon_column_width))
+                .align_x(Alignment::Center)
+                .align_y(Alignment::Center)
+                .height(view_model.row_height),
+            );
         } else {
             // Check if track is in library
             let is_in_library = track.1.metadata.id.as_ref().map_or(false, |track_id| {
@@ -8,6 +8,11 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone)]
 pub struct PlaybackState {
     pub session: Option<PlaybackSession>,
+    /// Tracks to play before continuing with the session order
+    pub queue: Vec<Track>,
+    /// The queued track that is playing, the session index stays on the last
+    /// session track so playback continues from there once the queue is empty
+    pub queue_current: Option<Track>,
     pub status: PlaybackStatus,
     pub progress: f32,
     pub now_playing: Option<MediaMetaData>,
@@ -18,6 +23,8 @@ impl PlaybackState {
     pub fn new() -> Self {
         Self {
             session: None,
+            queue: Vec::new(),
+            queue_current: None,
             status: PlaybackStatus::Stopped,
             progress: 0.0,
             now_playing: None,
@@ -66,6 +73,59 @@ impl PlaybackStatus {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+impl ReplayGainMode {
+    pub const ALL: &'static [ReplayGainMode] = &[
+        ReplayGainMode::Off,
+        ReplayGainMode::Track,
+        ReplayGainMode::Album,
+    ];
+}
+
+/// How ReplayGain tags are applied during playback
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayGainSettings {
+    pub mode: ReplayGainMode,
+    /// Extra gain in dB for tracks with ReplayGain tags
+    pub preamp: i32,
+    /// Lower the gain where the peak would otherwise clip
+    pub prevent_clipping: bool,
+}
+
+/// When the sleep timer stops playback
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SleepTimer {
+    /// After the given number of minutes
+    Minutes(u32),
+    EndOfTrack,
+    EndOfAlbum,
+}
+
+/// A playback session as saved between runs
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+pub struct SavedSession {
+    pub playlist_id: u32,
+    pub order: Vec<SavedEntry>,
+    pub index: usize,
+}
+
+/// A session track, referenced by its playlist entry id
+///
+/// The library and smart playlists are rebuilt on every launch with new entry
+/// ids, for those the track id is used instead.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+pub struct SavedEntry {
+    pub entry_id: u32,
+    pub track_id: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub enum RepeatMode {
     One,
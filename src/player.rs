@@ -1,44 +1,195 @@
 // SPDX-License-Identifier: GPL-3.0
 
+use crate::constants::{EQUALIZER_BAND_FREQUENCIES, EQUALIZER_MAX_GAIN};
 use crate::helpers::clamp;
 use gst::prelude::*;
 use gstreamer::{self as gst};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 
+/// Errors raised by the player
+#[derive(Debug, Clone)]
+pub enum PlayerError {
+    /// GStreamer or one of the pipeline elements isn't available
+    Init(String),
+    /// The pipeline refused to change to the given state
+    StateChange(gst::State),
+    /// Decoding or output failed while playing
+    Stream(String),
+}
+
+impl std::fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlayerError::Init(s) => write!(f, "Failed to initialize GStreamer: {}", s),
+            PlayerError::StateChange(state) => write!(f, "Failed to change state to {:?}", state),
+            PlayerError::Stream(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+impl From<gst::glib::Error> for PlayerError {
+    fn from(err: gst::glib::Error) -> Self {
+        PlayerError::Init(err.to_string())
+    }
+}
+
+impl From<gst::glib::BoolError> for PlayerError {
+    fn from(err: gst::glib::BoolError) -> Self {
+        PlayerError::Init(err.to_string())
+    }
+}
+
 pub struct Player {
     pub playbin: gst::Element,
+    // Only playbin is required, the stages below are left out when their
+    // elements aren't installed
+    // Volume stage in the audio-filter slot that applies ReplayGain
+    replay_gain: Option<gst::Element>,
+    // 10-band graphic equalizer after ReplayGain
+    equalizer: Option<gst::Element>,
+    // Volume stage after the equalizer used for crossfades
+    fade: Option<gst::Element>,
+    // Bin in the audio-sink slot, its sink is swapped to change the output
+    output: Option<(gst::Bin, Arc<Mutex<gst::Element>>)>,
+    // Elements that couldn't be created
+    missing: Vec<String>,
     queued_uri: Arc<Mutex<Option<String>>>,
+    // ReplayGain volume for the queued URI
+    queued_gain: Arc<Mutex<f64>>,
+    // ReplayGain volume to apply when the queued stream starts
+    pending_gain: Arc<Mutex<Option<f64>>>,
     about_to_finish_rx: mpsc::Receiver<()>,
 }
 
 impl Player {
-    pub fn new() -> Self {
-        match gst::init() {
-            Ok(_) => {}
-            Err(error) => {
-                panic!("Failed to initialize GStreamer: {:?}", error)
+    pub fn new() -> Result<Self, PlayerError> {
+        gst::init()?;
+
+        let playbin = gst::ElementFactory::make("playbin").build()?;
+
+        // Stages whose element isn't installed are left out
+        let mut missing = Vec::new();
+        let mut optional = |factory: &str, element: Result<gst::Element, gst::glib::BoolError>| {
+            if element.is_err() && !missing.iter().any(|m| m == factory) {
+                missing.push(factory.to_string());
+            }
+            element.ok()
+        };
+
+        let mut replay_gain = optional(
+            "volume",
+            gst::ElementFactory::make("volume")
+                .name("replay-gain")
+                .build(),
+        );
+        // Keeps the pitch when playing at another rate
+        let scaletempo = optional(
+            "scaletempo",
+            gst::ElementFactory::make("scaletempo").build(),
+        );
+        let mut equalizer = optional(
+            "equalizer-nbands",
+            gst::ElementFactory::make("equalizer-nbands")
+                .name("equalizer")
+                .property("num-bands", EQUALIZER_BAND_FREQUENCIES.len() as u32)
+                .build(),
+        );
+        let mut fade = optional(
+            "volume",
+            gst::ElementFactory::make("volume").name("fade").build(),
+        );
+
+        // scaletempo and the equalizer only take some sample formats
+        let mut convert = || {
+            optional(
+                "audioconvert",
+                gst::ElementFactory::make("audioconvert").build(),
+            )
+        };
+        let scaletempo = scaletempo.and_then(|scaletempo| Some([convert()?, scaletempo]));
+        let convert_eq = equalizer.as_ref().and_then(|_| convert());
+        if convert_eq.is_none() {
+            equalizer = None;
+        }
+
+        // Octave wide bands around the usual graphic equalizer frequencies
+        if let Some(bands) = equalizer
+            .as_ref()
+            .and_then(|e| e.dynamic_cast_ref::<gst::ChildProxy>())
+        {
+            for (index, frequency) in EQUALIZER_BAND_FREQUENCIES.iter().enumerate() {
+                if let Some(band) = bands.child_by_index(index as u32) {
+                    band.set_property("freq", *frequency as f64);
+                    band.set_property("bandwidth", *frequency as f64 * 0.707);
+                    band.set_property("gain", 0.0f64);
+                }
+            }
+        }
+
+        // replay-gain ! audioconvert ! scaletempo ! audioconvert ! equalizer ! fade,
+        // in the playbin audio-filter slot
+        let elements: Vec<gst::Element> = replay_gain
+            .iter()
+            .cloned()
+            .chain(scaletempo.into_iter().flatten())
+            .chain(convert_eq)
+            .chain(equalizer.iter().cloned())
+            .chain(fade.iter().cloned())
+            .collect();
+        if !elements.is_empty() {
+            match Self::audio_filter(&elements) {
+                Ok(audio_filter) => playbin.set_property("audio-filter", &audio_filter),
+                Err(err) => {
+                    eprintln!("Failed to build the audio filter: {}", err);
+                    replay_gain = None;
+                    equalizer = None;
+                    fade = None;
+                }
             }
         }
 
-        let playbin = gst::ElementFactory::make("playbin")
-            .build()
-            .expect("Failed to create playbin.");
+        // Without an output bin playbin picks the sink itself
+        let output = match Self::output() {
+            Ok((output, sink)) => {
+                playbin.set_property("audio-sink", &output);
+                Some((output, Arc::new(Mutex::new(sink))))
+            }
+            Err(err) => {
+                eprintln!("Failed to build the audio output: {}", err);
+                missing.push("autoaudiosink".to_string());
+                None
+            }
+        };
 
         let queued_uri: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+        let queued_gain: Arc<Mutex<f64>> = Arc::new(Mutex::new(1.0));
+        let pending_gain: Arc<Mutex<Option<f64>>> = Arc::new(Mutex::new(None));
         let (about_to_finish_tx, about_to_finish_rx) = mpsc::sync_channel::<()>(8);
 
         // Connect the about-to-finish signal for gapless playback.
         let queued_uri_clone = queued_uri.clone();
+        let queued_gain_clone = queued_gain.clone();
+        let pending_gain_clone = pending_gain.clone();
         playbin.connect("about-to-finish", false, move |args| {
-            let playbin_elem = args[0]
-                .get::<gst::Element>()
-                .expect("about-to-finish: invalid element arg");
+            let Ok(playbin_elem) = args[0].get::<gst::Element>() else {
+                return None;
+            };
 
             // If a next URI has been queued, set it now for seamless transition.
             if let Ok(guard) = queued_uri_clone.lock() {
                 if let Some(ref uri) = *guard {
                     playbin_elem.set_property("uri", uri);
+
+                    // The gain switches over when the new stream reaches the filter
+                    if let (Ok(queued), Ok(mut pending)) =
+                        (queued_gain_clone.lock(), pending_gain_clone.lock())
+                    {
+                        *pending = Some(*queued);
+                    }
+
                     // Notify the main thread that a gapless transition was queued.
                     let _ = about_to_finish_tx.try_send(());
                 }
@@ -47,46 +198,200 @@ impl Player {
             None
         });
 
-        Self {
+        // Apply the queued track's gain exactly where its stream begins, the
+        // previous track is still draining through the filter until then.
+        if let Some(pad) = replay_gain.as_ref().and_then(|e| e.static_pad("sink")) {
+            let pending_gain_clone = pending_gain.clone();
+            let replay_gain_weak = replay_gain.downgrade();
+            pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+                if let Some(gst::PadProbeData::Event(ref event)) = info.data {
+                    if event.type_() == gst::EventType::StreamStart {
+                        let gain = pending_gain_clone
+                            .lock()
+                            .ok()
+                            .and_then(|mut pending| pending.take());
+
+                        if let (Some(gain), Some(element)) = (gain, replay_gain_weak.upgrade()) {
+                            element.set_property("volume", gain);
+                        }
+                    }
+                }
+
+                gst::PadProbeReturn::Ok
+            });
+        }
+
+        Ok(Self {
             playbin,
+            replay_gain,
+            equalizer,
+            fade,
+            output,
+            missing,
             queued_uri,
+            queued_gain,
+            pending_gain,
             about_to_finish_rx,
+        })
+    }
+
+    /// Link filter stages into a bin for the playbin audio-filter slot
+    fn audio_filter(elements: &[gst::Element]) -> Result<gst::Bin, PlayerError> {
+        let audio_filter = gst::Bin::new();
+        audio_filter.add_many(elements)?;
+        gst::Element::link_many(elements)?;
+
+        let first = elements.first().and_then(|e| e.static_pad("sink"));
+        let last = elements.last().and_then(|e| e.static_pad("src"));
+        for target in [first, last] {
+            let target =
+                target.ok_or_else(|| PlayerError::Init("Missing filter pad".to_string()))?;
+            audio_filter.add_pad(&gst::GhostPad::with_target(&target)?)?;
         }
+
+        Ok(audio_filter)
+    }
+
+    /// The sink sits in a bin of its own so it can be replaced while playing
+    fn output() -> Result<(gst::Bin, gst::Element), PlayerError> {
+        let output = gst::Bin::new();
+        let sink = gst::ElementFactory::make("autoaudiosink").build()?;
+        output.add(&sink)?;
+        let target = sink
+            .static_pad("sink")
+            .ok_or_else(|| PlayerError::Init("Missing sink pad".to_string()))?;
+        output.add_pad(&gst::GhostPad::with_target(&target)?)?;
+
+        Ok((output, sink))
+    }
+
+    /// GStreamer elements that aren't installed, the features using them are off
+    pub fn missing_elements(&self) -> &[String] {
+        &self.missing
+    }
+
+    /// Whether crossfades can ramp the volume
+    pub fn can_fade(&self) -> bool {
+        self.fade.is_some()
     }
 
     pub fn load(&self, uri: &str) {
         self.playbin.set_property("uri", &uri);
     }
 
-    pub fn play(&mut self) {
-        match self.playbin.set_state(gst::State::Playing) {
-            Ok(_) => {}
-            Err(error) => {
-                panic!("Failed to play: {:?}", error);
-            }
+    pub fn play(&mut self) -> Result<(), PlayerError> {
+        self.set_state(gst::State::Playing)
+    }
+
+    pub fn pause(&mut self) -> Result<(), PlayerError> {
+        self.set_state(gst::State::Paused)
+    }
+
+    pub fn stop(&mut self) -> Result<(), PlayerError> {
+        self.set_state(gst::State::Null)
+    }
+
+    fn set_state(&self, state: gst::State) -> Result<(), PlayerError> {
+        self.playbin
+            .set_state(state)
+            .map(|_| ())
+            .map_err(|_| PlayerError::StateChange(state))
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        self.playbin.set_property("volume", clamp(volume, 0.0, 1.0));
+    }
+
+    /// Set the ReplayGain volume of the current track right away
+    pub fn set_replay_gain(&self, gain: f64) {
+        if let Ok(mut pending) = self.pending_gain.lock() {
+            *pending = None;
+        }
+        if let Some(replay_gain) = &self.replay_gain {
+            replay_gain.set_property("volume", gain);
         }
     }
 
-    pub fn pause(&mut self) {
-        match self.playbin.set_state(gst::State::Paused) {
-            Ok(_) => {}
-            Err(error) => {
-                panic!("Failed to pause: {:?}", error);
+    /// Set the equalizer band gains in dB, all zero bypasses the equalizer
+    pub fn set_equalizer(&self, gains: &[f64]) {
+        let Some(bands) = self
+            .equalizer
+            .as_ref()
+            .and_then(|e| e.dynamic_cast_ref::<gst::ChildProxy>())
+        else {
+            return;
+        };
+
+        let max = EQUALIZER_MAX_GAIN as f64;
+        for (index, gain) in gains.iter().enumerate() {
+            if let Some(band) = bands.child_by_index(index as u32) {
+                band.set_property("gain", clamp(*gain, -max, max));
             }
         }
     }
 
-    pub fn stop(&mut self) {
-        match self.playbin.set_state(gst::State::Null) {
-            Ok(_) => {}
-            Err(error) => {
-                panic!("Failed to stop: {:?}", error);
+    /// Play to another sink, `None` for the default output
+    ///
+    /// The sink is swapped once no data is passing into it, so playback carries
+    /// on without a restart.
+    pub fn set_output(&self, sink: Option<gst::Element>) -> Result<(), PlayerError> {
+        let sink = match sink {
+            Some(sink) => sink,
+            None => gst::ElementFactory::make("autoaudiosink").build()?,
+        };
+        let Some((output, current)) = &self.output else {
+            return Err(PlayerError::Init("Missing autoaudiosink".to_string()));
+        };
+        let pad = output
+            .static_pad("sink")
+            .ok_or_else(|| PlayerError::Init("Missing output pad".to_string()))?;
+
+        let output = output.downgrade();
+        let current = current.clone();
+        pad.add_probe(gst::PadProbeType::IDLE, move |pad, _| {
+            let (Some(output), Ok(mut current)) = (output.upgrade(), current.lock()) else {
+                return gst::PadProbeReturn::Remove;
+            };
+
+            let _ = current.set_state(gst::State::Null);
+            let _ = output.remove(&*current);
+
+            if output.add(&sink).is_ok() {
+                if let (Some(ghost), Some(target)) =
+                    (pad.downcast_ref::<gst::GhostPad>(), sink.static_pad("sink"))
+                {
+                    let _ = ghost.set_target(Some(&target));
+                }
+                let _ = sink.sync_state_with_parent();
+                *current = sink.clone();
             }
+
+            gst::PadProbeReturn::Remove
+        });
+
+        Ok(())
+    }
+
+    /// Set the crossfade volume, 0.0 to 1.0
+    pub fn set_fade(&self, volume: f64) {
+        if let Some(fade) = &self.fade {
+            fade.set_property("volume", clamp(volume, 0.0, 1.0));
         }
     }
 
-    pub fn set_volume(&mut self, volume: f64) {
-        self.playbin.set_property("volume", clamp(volume, 0.0, 1.0));
+    /// Discard pending bus messages, for a player that is fading out
+    pub fn drain_bus(&self) {
+        if let Some(bus) = self.playbin.bus() {
+            while bus.pop().is_some() {}
+        }
+        self.take_about_to_finish();
+    }
+
+    /// Set the ReplayGain volume for the queued URI
+    pub fn set_queued_gain(&self, gain: f64) {
+        if let Ok(mut guard) = self.queued_gain.lock() {
+            *guard = gain;
+        }
     }
 
     /// Set (or clear) the URI to be played gaplessly after the current track.
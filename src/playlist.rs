@@ -2,7 +2,8 @@
 
 use crate::app::{PlaylistKind, SortBy, SortDirection};
 use crate::fl;
-use crate::library::MediaMetaData;
+use crate::library::{Library, MediaMetaData};
+use crate::smart_playlist::SmartRules;
 use chrono::prelude::*;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -33,6 +34,12 @@ impl Playlist {
         }
     }
 
+    pub fn smart(name: String, rules: SmartRules) -> Playlist {
+        let mut playlist = Self::new(name);
+        playlist.kind = PlaylistKind::Smart(rules);
+        playlist
+    }
+
     pub fn library() -> Self {
         Self {
             id: u32::MAX,
@@ -50,6 +57,29 @@ impl Playlist {
         matches!(self.kind, PlaylistKind::Library)
     }
 
+    pub fn is_smart(&self) -> bool {
+        matches!(self.kind, PlaylistKind::Smart(_))
+    }
+
+    /// Rules of a smart playlist, `None` for other kinds
+    pub fn smart_rules(&self) -> Option<&SmartRules> {
+        match &self.kind {
+            PlaylistKind::Smart(rules) => Some(rules),
+            _ => None,
+        }
+    }
+
+    pub fn set_smart_rules(&mut self, rules: SmartRules) {
+        self.kind = PlaylistKind::Smart(rules);
+    }
+
+    /// Recompute the tracks of a smart playlist from the library
+    pub fn refresh(&mut self, library: &Library) {
+        if let PlaylistKind::Smart(rules) = &self.kind {
+            self.tracks = rules.apply(library, &self.tracks);
+        }
+    }
+
     pub fn id(&self) -> u32 {
         self.id
     }
@@ -66,6 +96,10 @@ impl Playlist {
         &self.tracks
     }
 
+    pub fn tracks_mut(&mut self) -> &mut [Track] {
+        &mut self.tracks
+    }
+
     pub fn len(&self) -> usize {
         self.tracks.len()
     }
@@ -137,6 +171,17 @@ impl Playlist {
         self.tracks.retain(|t| !t.selected);
     }
 
+    pub fn missing_count(&self) -> usize {
+        self.tracks.iter().filter(|t| t.missing).count()
+    }
+
+    /// Remove all tracks flagged as missing, returning how many were removed
+    pub fn remove_missing(&mut self) -> usize {
+        let before = self.tracks.len();
+        self.tracks.retain(|t| !t.missing);
+        before - self.tracks.len()
+    }
+
     pub fn selected_iter(&self) -> impl Iterator<Item = &Track> {
         self.tracks.iter().filter(|t| t.selected)
     }
@@ -176,6 +221,8 @@ pub struct Track {
     pub path: PathBuf,
     #[serde(skip)]
     pub selected: bool,
+    #[serde(skip)]
+    pub missing: bool,
     pub metadata: MediaMetaData,
     pub date_added: String,
 }
@@ -186,6 +233,7 @@ impl Default for Track {
             entry_id: rand::random(),
             path: PathBuf::new(),
             selected: false,
+            missing: false,
             metadata: MediaMetaData::new(),
             date_added: Local::now().to_string(),
         }
@@ -198,6 +246,7 @@ impl Track {
             entry_id: rand::random(),
             path: PathBuf::new(),
             selected: false,
+            missing: false,
             metadata: MediaMetaData::new(),
             date_added: Local::now().to_string(),
         }
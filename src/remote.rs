@@ -0,0 +1,394 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use crate::helpers::format_time;
+use crate::mpris::MprisCommand;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use tokio::sync::mpsc::UnboundedSender;
+use zbus::blocking::{Connection, Proxy, fdo::DBusProxy};
+use zbus::interface;
+use zbus::names::BusName;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+/// Bus name owned by the running instance, shared with MPRIS
+pub const BUS_NAME: &str = "org.mpris.MediaPlayer2.ethereal-waves";
+pub const OBJECT_PATH: &str = "/com/galacticpirateradio/EtherealWaves";
+const INTERFACE: &str = "com.galacticpirateradio.EtherealWaves";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+const USAGE: &str = "\
+Usage: ethereal-waves [FILE|FOLDER|PLAYLIST]...
+       ethereal-waves OPTION
+
+Control the running instance:
+  --play-pause              Toggle between playing and paused
+  --play, --pause, --stop   Start, pause or stop playback
+  --next, --previous        Skip to the next or previous track
+  --seek [+|-]SECONDS       Seek to a position, or by an offset when signed
+  --volume [+|-]PERCENT     Set the volume, or change it when signed
+  --status [--json]         Show what is playing
+  --enqueue PATH...         Add files, folders or playlists to the queue
+  --help                    Show this help";
+
+/// Commands from other invocations of the app that MPRIS has no place for
+pub struct Remote {
+    pub tx: UnboundedSender<MprisCommand>,
+}
+
+#[interface(name = "com.galacticpirateradio.EtherealWaves")]
+impl Remote {
+    /// Play absolute paths to files, folders and playlist files in a temporary
+    /// session and bring the window to the front
+    fn open(&self, paths: Vec<String>) {
+        if !paths.is_empty() {
+            let paths = paths.into_iter().map(PathBuf::from).collect();
+            let _ = self.tx.send(MprisCommand::Open(paths));
+        }
+        let _ = self.tx.send(MprisCommand::Raise);
+    }
+
+    /// Add absolute paths to files, folders and playlist files to the queue
+    fn enqueue(&self, paths: Vec<String>) {
+        let paths = paths.into_iter().map(PathBuf::from).collect();
+        let _ = self.tx.send(MprisCommand::Enqueue(paths));
+    }
+}
+
+/// Hand paths over to a running instance, returns false if none is running
+#[cfg(feature = "single-instance")]
+pub fn forward_open(paths: &[PathBuf]) -> bool {
+    let Ok(connection) = Connection::session() else {
+        return false;
+    };
+
+    connection
+        .call_method(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            Some(INTERFACE),
+            "Open",
+            &(path_strings(paths),),
+        )
+        .is_ok()
+}
+
+/// Run the command given by command-line options against the running
+/// instance, returns the exit status
+pub fn run(args: &[OsString]) -> i32 {
+    let command = match Command::parse(args) {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    match command.send() {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+#[derive(Debug)]
+enum RemoteError {
+    NotRunning,
+    NothingPlaying,
+    Bus(zbus::Error),
+}
+
+impl std::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteError::NotRunning => write!(f, "Ethereal Waves is not running"),
+            RemoteError::NothingPlaying => write!(f, "Nothing is playing"),
+            RemoteError::Bus(e) => write!(f, "D-Bus error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RemoteError {}
+
+impl From<zbus::Error> for RemoteError {
+    fn from(err: zbus::Error) -> Self {
+        RemoteError::Bus(err)
+    }
+}
+
+impl From<zbus::fdo::Error> for RemoteError {
+    fn from(err: zbus::fdo::Error) -> Self {
+        RemoteError::Bus(err.into())
+    }
+}
+
+/// A value to set, or to change the current one by when given with a sign
+#[derive(Clone, Copy, Debug)]
+enum Amount {
+    To(f64),
+    By(f64),
+}
+
+impl Amount {
+    fn parse(value: &str) -> Option<Self> {
+        let amount: f64 = value
+            .parse()
+            .ok()
+            .filter(|amount: &f64| amount.is_finite())?;
+
+        Some(if value.starts_with(['+', '-']) {
+            Amount::By(amount)
+        } else {
+            Amount::To(amount)
+        })
+    }
+
+    fn apply(self, current: f64) -> f64 {
+        match self {
+            Amount::To(value) => value,
+            Amount::By(change) => current + change,
+        }
+    }
+}
+
+/// A request for the running instance given as command-line options
+#[derive(Debug)]
+enum Command {
+    PlayPause,
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    /// In seconds
+    Seek(Amount),
+    /// In percent
+    Volume(Amount),
+    Status {
+        json: bool,
+    },
+    Enqueue(Vec<PathBuf>),
+}
+
+impl Command {
+    /// Parse the options following the program name, `None` asks for help
+    fn parse(args: &[OsString]) -> Result<Option<Self>, String> {
+        let Some((option, rest)) = args.split_first() else {
+            return Ok(None);
+        };
+        let option = option.to_string_lossy();
+
+        // Options that take no value
+        let simple = |command| {
+            if rest.is_empty() {
+                Ok(Some(command))
+            } else {
+                Err(format!("{} takes no value", option))
+            }
+        };
+
+        match option.as_ref() {
+            "--help" => Ok(None),
+            "--play-pause" => simple(Command::PlayPause),
+            "--play" => simple(Command::Play),
+            "--pause" => simple(Command::Pause),
+            "--stop" => simple(Command::Stop),
+            "--next" => simple(Command::Next),
+            "--previous" => simple(Command::Previous),
+            "--seek" => Self::amount(&option, rest).map(|amount| Some(Command::Seek(amount))),
+            "--volume" => Self::amount(&option, rest).map(|amount| Some(Command::Volume(amount))),
+            "--status" => match rest {
+                [] => Ok(Some(Command::Status { json: false })),
+                [json] if json == "--json" => Ok(Some(Command::Status { json: true })),
+                _ => Err(format!("{} only takes --json", option)),
+            },
+            "--enqueue" if rest.is_empty() => Err(format!("{} needs a path", option)),
+            "--enqueue" => Ok(Some(Command::Enqueue(
+                rest.iter()
+                    .map(|path| std::path::absolute(path).unwrap_or_else(|_| PathBuf::from(path)))
+                    .collect(),
+            ))),
+            _ => Err(format!("Unknown option {}", option)),
+        }
+    }
+
+    fn amount(option: &str, rest: &[OsString]) -> Result<Amount, String> {
+        match rest {
+            [value] => value
+                .to_str()
+                .and_then(Amount::parse)
+                .ok_or_else(|| format!("Invalid value for {}: {:?}", option, value)),
+            _ => Err(format!("{} takes one value", option)),
+        }
+    }
+
+    fn send(self) -> Result<(), RemoteError> {
+        let connection = Connection::session()?;
+
+        let name = BusName::try_from(BUS_NAME).map_err(zbus::Error::from)?;
+        if !DBusProxy::new(&connection)?.name_has_owner(name)? {
+            return Err(RemoteError::NotRunning);
+        }
+
+        let player = Proxy::new(&connection, BUS_NAME, MPRIS_PATH, PLAYER_INTERFACE)?;
+
+        match self {
+            Command::PlayPause => {
+                player.call_method("PlayPause", &())?;
+            }
+            Command::Play => {
+                player.call_method("Play", &())?;
+            }
+            Command::Pause => {
+                player.call_method("Pause", &())?;
+            }
+            Command::Stop => {
+                player.call_method("Stop", &())?;
+            }
+            Command::Next => {
+                player.call_method("Next", &())?;
+            }
+            Command::Previous => {
+                player.call_method("Previous", &())?;
+            }
+            Command::Seek(Amount::By(seconds)) => {
+                player.call_method("Seek", &((seconds * 1_000_000.0) as i64,))?;
+            }
+            Command::Seek(Amount::To(seconds)) => {
+                // Setting a position needs the id of the current track
+                let mut metadata: HashMap<String, OwnedValue> = player.get_property("Metadata")?;
+                let track_id = metadata
+                    .remove("mpris:trackid")
+                    .and_then(|value| OwnedObjectPath::try_from(value).ok())
+                    .ok_or(RemoteError::NothingPlaying)?;
+
+                player.call_method(
+                    "SetPosition",
+                    &(track_id, (seconds.max(0.0) * 1_000_000.0) as i64),
+                )?;
+            }
+            Command::Volume(amount) => {
+                let volume: f64 = player.get_property("Volume")?;
+                let volume = amount.apply(volume * 100.0).clamp(0.0, 100.0) / 100.0;
+                player.set_property("Volume", volume)?;
+            }
+            Command::Status { json } => {
+                let status = Status::read(&player)?;
+                if json {
+                    match serde_json::to_string(&status) {
+                        Ok(json) => println!("{}", json),
+                        Err(err) => eprintln!("Failed to serialize status: {}", err),
+                    }
+                } else {
+                    status.print();
+                }
+            }
+            Command::Enqueue(paths) => {
+                connection.call_method(
+                    Some(BUS_NAME),
+                    OBJECT_PATH,
+                    Some(INTERFACE),
+                    "Enqueue",
+                    &(path_strings(&paths),),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// What the running instance is playing, as shown by `--status`
+#[derive(Debug, Serialize)]
+struct Status {
+    status: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    /// Seconds
+    position: f64,
+    duration: Option<f64>,
+    /// Percent
+    volume: u32,
+    shuffle: bool,
+    loop_status: String,
+}
+
+impl Status {
+    fn read(player: &Proxy) -> Result<Self, RemoteError> {
+        let mut metadata: HashMap<String, OwnedValue> = player.get_property("Metadata")?;
+        let mut text = |key: &str| {
+            metadata
+                .remove(key)
+                .and_then(|value| String::try_from(value).ok())
+        };
+        let title = text("xesam:title");
+        let album = text("xesam:album");
+
+        let artist = metadata
+            .remove("xesam:artist")
+            .and_then(|value| Vec::<String>::try_from(value).ok())
+            .map(|artists| artists.join(", "));
+        let duration = metadata
+            .remove("mpris:length")
+            .and_then(|value| i64::try_from(value).ok())
+            .map(|length| length as f64 / 1_000_000.0);
+
+        let position: i64 = player.get_property("Position")?;
+        let volume: f64 = player.get_property("Volume")?;
+
+        Ok(Self {
+            status: player.get_property("PlaybackStatus")?,
+            title,
+            artist,
+            album,
+            position: position as f64 / 1_000_000.0,
+            duration,
+            volume: (volume * 100.0).round() as u32,
+            shuffle: player.get_property("Shuffle")?,
+            loop_status: player.get_property("LoopStatus")?,
+        })
+    }
+
+    fn print(&self) {
+        println!("{}", self.status);
+
+        for (label, value) in [
+            ("Title", &self.title),
+            ("Artist", &self.artist),
+            ("Album", &self.album),
+        ] {
+            if let Some(value) = value {
+                println!("{}: {}", label, value);
+            }
+        }
+
+        match self.duration {
+            Some(duration) => println!(
+                "Position: {} / {}",
+                format_time(self.position as f32),
+                format_time(duration as f32)
+            ),
+            None => println!("Position: {}", format_time(self.position as f32)),
+        }
+
+        println!("Volume: {}%", self.volume);
+        println!("Shuffle: {}", if self.shuffle { "on" } else { "off" });
+        println!("Repeat: {}", self.loop_status);
+    }
+}
+
+fn path_strings(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
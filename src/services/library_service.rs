@@ -3,15 +3,17 @@
 
 use crate::constants::*;
 use crate::library::{Library, MediaMetaData};
+use crate::services::playlist_codec;
 use gstreamer as gst;
 use gstreamer_pbutils as pbutils;
 use sha256::digest;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc::UnboundedSender;
 use tokio_util::sync::CancellationToken;
 use url::Url;
@@ -29,11 +31,72 @@ pub enum LibraryProgress {
     },
     /// Partial library update with completed entries
     PartialUpdate(HashMap<PathBuf, MediaMetaData>),
+    /// Counts of files added, updated, moved and removed compared to the previous library
+    Changes {
+        added: usize,
+        updated: usize,
+        moved: usize,
+        removed: usize,
+    },
     /// Final complete library
     Complete(Library),
     Cancelled,
 }
 
+/// File system changes detected under the library paths
+#[derive(Debug, Clone, Default)]
+pub struct LibraryChanges {
+    /// Files or directories that were created, modified or moved into place
+    pub changed: HashSet<PathBuf>,
+    /// Files or directories that were deleted or moved away
+    pub removed: HashSet<PathBuf>,
+}
+
+impl LibraryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn add_changed(&mut self, path: PathBuf) {
+        self.removed.remove(&path);
+        self.changed.insert(path);
+    }
+
+    pub fn add_removed(&mut self, path: PathBuf) {
+        self.changed.remove(&path);
+        self.removed.insert(path);
+    }
+
+    /// Fold later changes into this set, later events win
+    pub fn merge(&mut self, other: LibraryChanges) {
+        for path in other.removed {
+            self.add_removed(path);
+        }
+        for path in other.changed {
+            self.add_changed(path);
+        }
+    }
+}
+
+/// Work for a single scan: the files found on disk, the previous entries they are
+/// compared against and the entries that are carried over untouched
+struct ScanPlan {
+    files: HashMap<PathBuf, (u64, u64)>,
+    previous: HashMap<PathBuf, MediaMetaData>,
+    kept: HashMap<PathBuf, MediaMetaData>,
+    mode: ScanMode,
+    workers: usize,
+}
+
+/// How much of the library a scan should re-examine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanMode {
+    /// Re-extract metadata for every file
+    Full,
+    /// Only re-extract metadata for new or modified files
+    Partial,
+}
+
 #[derive(Debug)]
 pub enum LibraryError {
     Io(std::io::Error),
@@ -104,153 +167,535 @@ impl LibraryService {
     ///
     /// This spawns a thread that:
     /// 1. Walks all provided paths to find audio files
-    /// 2. Extracts metadata using GStreamer
-    /// 3. Caches artwork
-    /// 4. Sends progress updates via the channel
+    /// 2. Compares them against the previous library
+    /// 3. Extracts metadata using GStreamer on a pool of `workers` threads
+    /// 4. Caches artwork
+    /// 5. Sends progress updates via the channel
+    ///
+    /// In `ScanMode::Partial`, files whose modification time and size match the
+    /// previous library keep their stored metadata and are not rediscovered.
     pub fn scan_library(
         paths: HashSet<String>,
+        previous: Library,
+        mode: ScanMode,
+        workers: usize,
         xdg_dirs: Arc<BaseDirectories>,
         progress_tx: UnboundedSender<LibraryProgress>,
         cancel_token: CancellationToken,
     ) {
         std::thread::spawn(move || {
-            let mut library = Library::new();
-
             // Step 1: Collect all audio file paths
-            for path in paths {
-                if cancel_token.is_cancelled() {
-                    log::info!("Library scan cancelled by user");
-                    let _ = progress_tx.send(LibraryProgress::Cancelled);
-                    return;
-                }
+            let Some(files) = Self::collect_audio_files(&paths, &cancel_token) else {
+                log::info!("Library scan cancelled by user");
+                let _ = progress_tx.send(LibraryProgress::Cancelled);
+                return;
+            };
+
+            let plan = ScanPlan {
+                files,
+                previous: previous.media,
+                kept: HashMap::new(),
+                mode,
+                workers,
+            };
+
+            Self::run_scan(plan, &xdg_dirs, &progress_tx, &cancel_token);
+        });
+    }
+
+    /// Apply file system changes reported by the library watcher in a background thread
+    ///
+    /// Only the changed paths are walked and rediscovered, everything else in the
+    /// previous library is kept as is. Progress is reported the same way as a scan.
+    pub fn apply_changes(
+        changes: LibraryChanges,
+        previous: Library,
+        workers: usize,
+        xdg_dirs: Arc<BaseDirectories>,
+        progress_tx: UnboundedSender<LibraryProgress>,
+        cancel_token: CancellationToken,
+    ) {
+        std::thread::spawn(move || {
+            let mut kept = previous.media;
+
+            // Entries under removed or changed paths are compared against what is on
+            // disk now, so files moved within the library can be recognized
+            let previous: HashMap<PathBuf, MediaMetaData> = kept
+                .extract_if(|path, _| {
+                    changes
+                        .removed
+                        .iter()
+                        .chain(changes.changed.iter())
+                        .any(|p| path.starts_with(p))
+                })
+                .collect();
+
+            let Some(files) = Self::collect_audio_files(&changes.changed, &cancel_token) else {
+                log::info!("Library change update cancelled");
+                let _ = progress_tx.send(LibraryProgress::Cancelled);
+                return;
+            };
+
+            let plan = ScanPlan {
+                files,
+                previous,
+                kept,
+                mode: ScanMode::Partial,
+                workers,
+            };
+
+            Self::run_scan(plan, &xdg_dirs, &progress_tx, &cancel_token);
+        });
+    }
+
+    /// Compare the collected files against the previous entries, extract metadata
+    /// where needed and send the resulting library through the progress channel
+    fn run_scan(
+        plan: ScanPlan,
+        xdg_dirs: &BaseDirectories,
+        progress_tx: &UnboundedSender<LibraryProgress>,
+        cancel_token: &CancellationToken,
+    ) {
+        // Step 2: Compare against the previous library
+        let mut previous_media = plan.previous;
+        let mut completed_entries: HashMap<PathBuf, MediaMetaData> = plan.kept;
+        let mut entries: Vec<(PathBuf, MediaMetaData)> = Vec::new();
+        let mut added_paths: HashSet<PathBuf> = HashSet::new();
+        let mut updated: usize = 0;
+        let mut moved: usize = 0;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        for (path, (modified, size)) in plan.files {
+            let mut metadata = MediaMetaData::new();
 
-                for entry in WalkDir::new(&path).into_iter().filter_map(|e| e.ok()) {
-                    let extension = entry
-                        .file_name()
-                        .to_str()
-                        .unwrap_or("")
-                        .split('.')
-                        .last()
-                        .unwrap_or("")
-                        .to_lowercase();
-
-                    let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
-
-                    if VALID_AUDIO_EXTENSIONS.contains(&extension.as_str()) && size > MIN_FILE_SIZE
-                    {
-                        library
-                            .media
-                            .insert(entry.into_path(), MediaMetaData::new());
+            match previous_media.remove(&path) {
+                Some(mut previous)
+                    if plan.mode == ScanMode::Partial && previous.is_unchanged(modified, size) =>
+                {
+                    // Entries from before content hashes were stored get one once,
+                    // so a later move of the file can be recognized
+                    if previous.content_hash.is_none() {
+                        previous.content_hash = Self::content_hash(&path).ok();
                     }
+                    completed_entries.insert(path, previous);
+                    continue;
+                }
+                Some(previous) => {
+                    updated += 1;
+                    // Entries from before the date was tracked fall back to the file time
+                    metadata.date_added = previous.date_added.or(Some(modified));
+                    // Keep the id so playlists and the session still find the file
+                    metadata.id = previous.id;
+                    // Analyzed loudness is only in the library, extraction keeps it
+                    // as long as the audio is the same
+                    metadata.content_hash = previous.content_hash;
+                    metadata.track_gain = previous.track_gain;
+                    metadata.track_peak = previous.track_peak;
+                    metadata.album_gain = previous.album_gain;
+                    metadata.album_peak = previous.album_peak;
+                }
+                None => {
+                    added_paths.insert(path.clone());
+                    metadata.date_added = Some(now);
                 }
             }
 
-            // Step 2: Extract metadata from each file
-            if let Err(err) = gst::init() {
-                eprintln!("Failed to initialize GStreamer: {}", err);
-                if progress_tx
-                    .send(LibraryProgress::Progress {
-                        current: 0.0,
-                        total: 0.0,
-                        percent: 0.0,
-                    })
-                    .is_err()
-                {
-                    log::warn!("Failed to send progress update")
+            metadata.file_modified = Some(modified);
+            metadata.file_size = Some(size);
+            entries.push((path, metadata));
+        }
+
+        // Files that were moved or renamed are recognized by their content hash and
+        // keep their id, date added and loudness. If their modification time and size
+        // are unchanged as well a partial scan carries the metadata over without
+        // rediscovering them.
+        if !previous_media.is_empty() && !added_paths.is_empty() {
+            let mut by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+            for (path, metadata) in &previous_media {
+                if let Some(content_hash) = &metadata.content_hash {
+                    by_hash
+                        .entry(content_hash.clone())
+                        .or_default()
+                        .push(path.clone());
+                }
+            }
+
+            entries.retain_mut(|(path, metadata)| {
+                if by_hash.is_empty() || !added_paths.contains(path) {
+                    return true;
+                }
+
+                let Ok(content_hash) = Self::content_hash(path) else {
+                    return true;
                 };
-                if progress_tx
-                    .send(LibraryProgress::Complete(library))
-                    .is_err()
-                {
-                    log::warn!("Failed to send completion update")
+
+                let Some(candidates) = by_hash.get_mut(&content_hash) else {
+                    return true;
                 };
-                return;
-            }
 
-            let mut update_progress: f32 = 0.0;
-            let update_total: f32 = library.media.len() as f32;
+                let modified = metadata.file_modified.unwrap_or_default();
+                let size = metadata.file_size.unwrap_or_default();
+
+                // Identical copies share a hash, prefer the one with the same stamp
+                let position = candidates
+                    .iter()
+                    .position(|old| {
+                        previous_media
+                            .get(old)
+                            .is_some_and(|m| m.is_unchanged(modified, size))
+                    })
+                    .unwrap_or(0);
+
+                let old_path = candidates.swap_remove(position);
+                if candidates.is_empty() {
+                    by_hash.remove(&content_hash);
+                }
 
-            let mut last_progress_update = Instant::now();
-            let update_progress_interval = Duration::from_millis(PROGRESS_UPDATE_INTERVAL_MS);
+                let Some(previous) = previous_media.remove(&old_path) else {
+                    return true;
+                };
 
-            let mut last_library_update = Instant::now();
-            let update_library_interval = Duration::from_secs(LIBRARY_UPDATE_INTERVAL_SECS);
+                added_paths.remove(path);
+                moved += 1;
 
-            let mut entries: Vec<(PathBuf, MediaMetaData)> = library.media.into_iter().collect();
+                if plan.mode == ScanMode::Partial && previous.is_unchanged(modified, size) {
+                    completed_entries.insert(path.clone(), previous);
+                    return false;
+                }
 
-            let mut completed_entries: HashMap<PathBuf, MediaMetaData> = HashMap::new();
+                metadata.id = previous.id;
+                metadata.date_added = previous.date_added.or(metadata.date_added);
+                metadata.content_hash = previous.content_hash;
+                metadata.track_gain = previous.track_gain;
+                metadata.track_peak = previous.track_peak;
+                metadata.album_gain = previous.album_gain;
+                metadata.album_peak = previous.album_peak;
+                true
+            });
+        }
 
-            let discoverer = match pbutils::Discoverer::new(gst::ClockTime::from_seconds(
-                GSTREAMER_TIMEOUT_SECS,
-            ))
-            .map_err(|e| format!("Failed to create discoverer: {:?}", e))
-            .ok()
+        let added = added_paths.len();
+
+        // Anything left over no longer exists on disk
+        let removed = previous_media.len();
+
+        // Step 3: Extract metadata from each new or modified file
+        if let Err(err) = gst::init() {
+            eprintln!("Failed to initialize GStreamer: {}", err);
+            if progress_tx
+                .send(LibraryProgress::Progress {
+                    current: 0.0,
+                    total: 0.0,
+                    percent: 0.0,
+                })
+                .is_err()
             {
-                Some(discoverer) => discoverer,
-                None => {
-                    eprintln!("Failed to create discoverer");
+                log::warn!("Failed to send progress update")
+            };
+            let mut out = Library::new();
+            out.media = completed_entries;
+            if progress_tx.send(LibraryProgress::Complete(out)).is_err() {
+                log::warn!("Failed to send completion update")
+            };
+            return;
+        }
+
+        let mut update_progress: f32 = 0.0;
+        let update_total: f32 = entries.len() as f32;
+
+        let mut last_progress_update = Instant::now();
+        let update_progress_interval = Duration::from_millis(PROGRESS_UPDATE_INTERVAL_MS);
+
+        let mut last_library_update = Instant::now();
+        let update_library_interval = Duration::from_secs(LIBRARY_UPDATE_INTERVAL_SECS);
+
+        // One discoverer per worker, they are not shared between threads
+        let workers = plan.workers.clamp(1, entries.len().max(1));
+        let mut discoverers = Vec::with_capacity(workers);
+
+        for _ in 0..workers {
+            match pbutils::Discoverer::new(gst::ClockTime::from_seconds(GSTREAMER_TIMEOUT_SECS)) {
+                Ok(discoverer) => discoverers.push(discoverer),
+                Err(err) => {
+                    eprintln!("Failed to create discoverer: {:?}", err);
                     let _ = progress_tx.send(LibraryProgress::Cancelled);
                     return;
                 }
-            };
+            }
+        }
+
+        let next_entry = AtomicUsize::new(0);
+        let (result_tx, result_rx) = mpsc::channel::<(usize, Result<MediaMetaData, String>)>();
+
+        let cancelled = std::thread::scope(|scope| {
+            for discoverer in discoverers {
+                let result_tx = result_tx.clone();
+                let next_entry = &next_entry;
+                let entries = &entries;
+
+                scope.spawn(move || {
+                    loop {
+                        if cancel_token.is_cancelled() {
+                            break;
+                        }
+
+                        let index = next_entry.fetch_add(1, Ordering::Relaxed);
+                        let Some((file, track_metadata)) = entries.get(index) else {
+                            break;
+                        };
+
+                        let mut track_metadata = track_metadata.clone();
+                        let result = Self::extract_metadata(
+                            file,
+                            &mut track_metadata,
+                            xdg_dirs,
+                            &discoverer,
+                        )
+                        .map(|_| track_metadata);
+
+                        if result_tx.send((index, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            // Only the workers hold senders now, so the loop below ends when they finish
+            drop(result_tx);
 
-            for (file, track_metadata) in entries.iter_mut() {
+            // Results are applied in entry order, the same order a serial scan uses,
+            // so progress and partial updates are reproducible
+            let mut out_of_order: BTreeMap<usize, Result<MediaMetaData, String>> = BTreeMap::new();
+            let mut next_index: usize = 0;
+
+            for (index, result) in result_rx.iter() {
                 if cancel_token.is_cancelled() {
-                    log::info!("Library scan cancelled during metadata extraction");
-                    let _ = progress_tx.send(LibraryProgress::Cancelled);
-                    return;
+                    return true;
                 }
 
-                // Always count this file as processed (attempted)
-                update_progress += 1.0;
+                out_of_order.insert(index, result);
 
-                let ok = match Self::extract_metadata(file, track_metadata, &xdg_dirs, &discoverer)
-                {
-                    Ok(_) => true,
-                    Err(e) => {
-                        eprintln!("Failed to extract metadata from {:?}: {}", file, e);
-                        false
+                while let Some(result) = out_of_order.remove(&next_index) {
+                    let file = &entries[next_index].0;
+                    next_index += 1;
+
+                    // Always count this file as processed (attempted)
+                    update_progress += 1.0;
+
+                    match result {
+                        Ok(track_metadata) => {
+                            completed_entries.insert(file.clone(), track_metadata);
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to extract metadata from {:?}: {}", file, e);
+                        }
                     }
-                };
 
-                if ok {
-                    completed_entries.insert(file.clone(), track_metadata.clone());
+                    let now = Instant::now();
+
+                    if now.duration_since(last_progress_update) >= update_progress_interval {
+                        last_progress_update = now;
+                        let _ = progress_tx.send(LibraryProgress::Progress {
+                            current: update_progress,
+                            total: update_total,
+                            percent: if update_total > 0.0 {
+                                update_progress / update_total * 100.0
+                            } else {
+                                100.0
+                            },
+                        });
+                    }
+
+                    if now.duration_since(last_library_update) >= update_library_interval {
+                        last_library_update = now;
+                        let _ = progress_tx
+                            .send(LibraryProgress::PartialUpdate(completed_entries.clone()));
+                    }
+                }
+            }
+
+            cancel_token.is_cancelled()
+        });
+
+        if cancelled {
+            log::info!("Library scan cancelled during metadata extraction");
+            let _ = progress_tx.send(LibraryProgress::Cancelled);
+            return;
+        }
+
+        // Ensure UI reaches 100% and finishes
+        let _ = progress_tx.send(LibraryProgress::Progress {
+            current: update_total,
+            total: update_total,
+            percent: 100.0,
+        });
+
+        let _ = progress_tx.send(LibraryProgress::Changes {
+            added,
+            updated,
+            moved,
+            removed,
+        });
+
+        let mut out = Library::new();
+        out.media = completed_entries;
+
+        let _ = progress_tx.send(LibraryProgress::Complete(out));
+    }
+
+    /// Number of metadata workers to use for a configured value, where 0 means
+    /// one per available CPU up to `MAX_AUTO_SCAN_WORKERS`
+    pub fn worker_count(configured: usize) -> usize {
+        if configured > 0 {
+            return configured;
+        }
+
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_AUTO_SCAN_WORKERS)
+    }
+
+    /// Read files opened directly, such as from the command line, in the order given
+    ///
+    /// Folders are walked for audio files in name order and playlist files are
+    /// replaced by their entries. Files in the library keep their stored
+    /// metadata, anything else is discovered. Files that can't be read are left out.
+    pub fn open_files(
+        paths: Vec<PathBuf>,
+        library: &Library,
+        xdg_dirs: &BaseDirectories,
+    ) -> Vec<(PathBuf, MediaMetaData)> {
+        let mut files = Vec::new();
+        for path in &paths {
+            Self::expand_path(path, &mut files);
+        }
+
+        let discoverer =
+            match pbutils::Discoverer::new(gst::ClockTime::from_seconds(GSTREAMER_TIMEOUT_SECS)) {
+                Ok(discoverer) => discoverer,
+                Err(err) => {
+                    eprintln!("Failed to create discoverer: {:?}", err);
+                    return Vec::new();
+                }
+            };
+
+        files
+            .into_iter()
+            .filter_map(|file| {
+                let canonical = fs::canonicalize(&file).unwrap_or_else(|_| file.clone());
+
+                if let Some((path, metadata)) = library
+                    .media
+                    .get_key_value(&file)
+                    .or_else(|| library.media.get_key_value(&canonical))
+                {
+                    return Some((path.clone(), metadata.clone()));
                 }
 
-                let now = Instant::now();
-
-                if now.duration_since(last_progress_update) >= update_progress_interval {
-                    last_progress_update = now;
-                    let _ = progress_tx.send(LibraryProgress::Progress {
-                        current: update_progress,
-                        total: update_total,
-                        percent: if update_total > 0.0 {
-                            update_progress / update_total * 100.0
-                        } else {
-                            100.0
-                        },
-                    });
+                let mut metadata = MediaMetaData::new();
+                match Self::extract_metadata(&canonical, &mut metadata, xdg_dirs, &discoverer) {
+                    Ok(()) => Some((canonical, metadata)),
+                    Err(err) => {
+                        eprintln!("Failed to open {:?}: {}", file, err);
+                        None
+                    }
                 }
+            })
+            .collect()
+    }
+
+    /// Add the audio files a path stands for
+    fn expand_path(path: &Path, files: &mut Vec<PathBuf>) {
+        if path.is_dir() {
+            files.extend(
+                WalkDir::new(path)
+                    .sort_by_file_name()
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file() && Self::is_audio_file(e.path()))
+                    .map(|e| e.into_path()),
+            );
+        } else if let Some(codec) = playlist_codec::for_path(path) {
+            let document = match fs::read(path) {
+                Ok(bytes) => codec.decode(&String::from_utf8_lossy(&bytes)),
+                Err(err) => Err(err.into()),
+            };
 
-                if now.duration_since(last_library_update) >= update_library_interval {
-                    last_library_update = now;
-                    let _ =
-                        progress_tx.send(LibraryProgress::PartialUpdate(completed_entries.clone()));
+            match document {
+                Ok(document) => {
+                    let base_dir = path.parent().unwrap_or(Path::new("/"));
+                    files.extend(document.entries.iter().filter_map(|entry| {
+                        playlist_codec::resolve_location(&entry.location, base_dir)
+                    }));
                 }
+                Err(err) => eprintln!("Failed to read playlist {:?}: {}", path, err),
             }
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
 
-            // Ensure UI reaches 100% and finishes
-            let _ = progress_tx.send(LibraryProgress::Progress {
-                current: update_total,
-                total: update_total,
-                percent: 100.0,
-            });
+    /// Walk the library paths and collect every audio file with its modification
+    /// time and size. Returns `None` if the scan was cancelled.
+    fn collect_audio_files<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+        cancel_token: &CancellationToken,
+    ) -> Option<HashMap<PathBuf, (u64, u64)>> {
+        let mut files = HashMap::new();
 
-            let mut out = Library::new();
-            out.media = completed_entries;
+        for path in paths {
+            if cancel_token.is_cancelled() {
+                return None;
+            }
 
-            let _ = progress_tx.send(LibraryProgress::Complete(out));
-        });
+            for entry in WalkDir::new(path.as_ref())
+                .into_iter()
+                .filter_map(|e| e.ok())
+            {
+                if !Self::is_audio_file(entry.path()) {
+                    continue;
+                }
+
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+
+                let size = metadata.len();
+                if size <= MIN_FILE_SIZE {
+                    continue;
+                }
+
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+
+                files.insert(entry.into_path(), (modified, size));
+            }
+        }
+
+        Some(files)
+    }
+
+    /// Whether the file has one of the supported audio extensions
+    fn is_audio_file(path: &Path) -> bool {
+        let extension = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("")
+            .split('.')
+            .last()
+            .unwrap_or("")
+            .to_lowercase();
+
+        VALID_AUDIO_EXTENSIONS.contains(&extension.as_str())
     }
 
     /// Extract metadata from a single audio file using GStreamer
@@ -270,8 +715,28 @@ impl LibraryService {
             .discover_uri(uri.as_str())
             .map_err(|e| format!("Failed to discover: {}", e))?;
 
-        // Set the unique ID
-        track_metadata.id = Some(digest(file_str));
+        // A file keeps its id as long as it is known, moved files carry theirs along.
+        // New files get one from the path and the time they were found, so copies
+        // with the same contents are still told apart.
+        if track_metadata.id.is_none() {
+            let found = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default();
+            track_metadata.id = Some(digest(format!("{}:{}", file_str, found)));
+        }
+
+        let content_hash =
+            Self::content_hash(file).map_err(|e| format!("Failed to read file: {}", e))?;
+
+        // Loudness carried over from the previous scan only applies to the same audio
+        if track_metadata.content_hash.as_deref() != Some(content_hash.as_str()) {
+            track_metadata.track_gain = None;
+            track_metadata.track_peak = None;
+            track_metadata.album_gain = None;
+            track_metadata.album_peak = None;
+        }
+        track_metadata.content_hash = Some(content_hash);
 
         // Extract tags if available
         if let Some(tags) = info.tags() {
@@ -295,6 +760,16 @@ impl LibraryService {
                 .get::<gst::tags::AlbumVolumeCount>()
                 .map(|t| t.get().to_owned());
 
+            // ReplayGain, tags take precedence over analyzed values
+            if let Some(gain) = tags.get::<gst::tags::TrackGain>() {
+                track_metadata.track_gain = Some(gain.get());
+                track_metadata.track_peak = tags.get::<gst::tags::TrackPeak>().map(|t| t.get());
+            }
+            if let Some(gain) = tags.get::<gst::tags::AlbumGain>() {
+                track_metadata.album_gain = Some(gain.get());
+                track_metadata.album_peak = tags.get::<gst::tags::AlbumPeak>().map(|t| t.get());
+            }
+
             // Duration
             if let Some(duration) = info.duration() {
                 track_metadata.duration = Some(duration.seconds() as f32);
@@ -316,6 +791,105 @@ impl LibraryService {
         Ok(())
     }
 
+    /// Hash a file's audio payload, used to recognize it after a move
+    ///
+    /// Leading ID3v2 tags, FLAC metadata blocks and a trailing ID3v1 tag are skipped
+    /// so retagging those files keeps the hash. Payloads larger than three
+    /// samples are hashed from their start, middle and end instead of in full.
+    fn content_hash(file: &Path) -> std::io::Result<String> {
+        let mut reader = File::open(file)?;
+        let len = reader.metadata()?.len();
+
+        let mut start = Self::payload_start(&mut reader)?;
+        let mut end = len;
+
+        // ID3v1 tag in the last 128 bytes
+        if end >= 128 {
+            let mut tag = [0u8; 3];
+            reader.seek(SeekFrom::Start(end - 128))?;
+            reader.read_exact(&mut tag)?;
+            if &tag == b"TAG" {
+                end -= 128;
+            }
+        }
+
+        if start >= end {
+            start = 0;
+            end = len;
+        }
+
+        let payload_len = end - start;
+        let mut buffer = payload_len.to_le_bytes().to_vec();
+
+        let sample = CONTENT_HASH_SAMPLE_BYTES;
+        let ranges = if payload_len <= sample * 3 {
+            vec![(start, payload_len)]
+        } else {
+            vec![
+                (start, sample),
+                (start + (payload_len - sample) / 2, sample),
+                (end - sample, sample),
+            ]
+        };
+
+        for (offset, length) in ranges {
+            let mut chunk = vec![0u8; length as usize];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut chunk)?;
+            buffer.extend_from_slice(&chunk);
+        }
+
+        Ok(digest(buffer.as_slice()))
+    }
+
+    /// Offset of the first byte after any leading ID3v2 tags or FLAC metadata blocks
+    fn payload_start(reader: &mut File) -> std::io::Result<u64> {
+        let mut offset: u64 = 0;
+        let mut header = [0u8; 10];
+
+        // ID3v2 tags, there may be more than one
+        loop {
+            reader.seek(SeekFrom::Start(offset))?;
+            if reader.read_exact(&mut header).is_err() {
+                return Ok(offset);
+            }
+
+            if &header[0..3] != b"ID3" {
+                break;
+            }
+
+            // Tag size is stored as a 28 bit syncsafe integer
+            let size = header[6..10]
+                .iter()
+                .fold(0u64, |acc, b| (acc << 7) | (*b as u64 & 0x7f));
+            let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+            offset += 10 + size + footer;
+        }
+
+        // FLAC metadata blocks, a truncated block list hashes from the stream marker
+        if &header[0..4] == b"fLaC" {
+            let stream_start = offset;
+            offset += 4;
+            loop {
+                let mut block = [0u8; 4];
+                reader.seek(SeekFrom::Start(offset))?;
+                if reader.read_exact(&mut block).is_err() {
+                    return Ok(stream_start);
+                }
+
+                let is_last = block[0] & 0x80 != 0;
+                let length = u32::from_be_bytes([0, block[1], block[2], block[3]]) as u64;
+                offset += 4 + length;
+
+                if is_last {
+                    break;
+                }
+            }
+        }
+
+        Ok(offset)
+    }
+
     /// Cache album artwork to disk, avoiding duplicates
     fn cache_artwork(sample: gst::Sample, xdg_dirs: BaseDirectories) -> Option<String> {
         let buffer = sample.buffer()?;
@@ -0,0 +1,231 @@
+// SPDX-License-Identifier: GPL-3.0
+// src/services/library_watcher.rs
+
+use crate::constants::*;
+use crate::services::library_service::LibraryChanges;
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Watches the library paths with inotify and reports debounced changes
+pub struct LibraryWatcher;
+
+impl LibraryWatcher {
+    /// Watch the library roots in a background thread
+    ///
+    /// Raw events are collected until the file system has been quiet for
+    /// `WATCHER_DEBOUNCE_MS`, then sent as a single `LibraryChanges` batch.
+    /// Roots are checked periodically so an unmounted root stops being watched
+    /// without its tracks being dropped, and a remounted root is watched again
+    /// and reported as changed so anything modified in the meantime is picked up.
+    /// The thread exits once the receiving end of the channel is dropped.
+    pub fn spawn(roots: Vec<PathBuf>, changes_tx: UnboundedSender<LibraryChanges>) {
+        std::thread::spawn(move || {
+            let (event_tx, event_rx) = mpsc::channel::<notify::Result<Event>>();
+
+            let mut watcher = match notify::recommended_watcher(event_tx) {
+                Ok(watcher) => watcher,
+                Err(err) => {
+                    eprintln!("Failed to create library watcher: {}", err);
+                    return;
+                }
+            };
+
+            // Device id of each root that is currently being watched
+            let mut watched: HashMap<PathBuf, u64> = HashMap::new();
+            // Roots seen as mount points of their own
+            let mut mount_points: HashSet<PathBuf> = HashSet::new();
+            let mut first_check = true;
+            let mut last_root_check = Instant::now();
+
+            let mut pending = LibraryChanges::default();
+            let mut last_event: Option<Instant> = None;
+
+            let debounce = Duration::from_millis(WATCHER_DEBOUNCE_MS);
+            let poll_interval = Duration::from_millis(WATCHER_POLL_INTERVAL_MS);
+            let root_check_interval = Duration::from_secs(WATCHER_ROOT_CHECK_SECS);
+
+            loop {
+                if changes_tx.is_closed() {
+                    break;
+                }
+
+                if first_check || last_root_check.elapsed() >= root_check_interval {
+                    last_root_check = Instant::now();
+
+                    for root in &roots {
+                        let device = Self::root_device(root, &mount_points);
+                        if device.is_some_and(|device| Self::is_mount_point(root, device)) {
+                            mount_points.insert(root.clone());
+                        }
+
+                        match (watched.get(root).copied(), device) {
+                            // Root appeared or was mounted again
+                            (None, Some(device)) => {
+                                if let Err(err) = watcher.watch(root, RecursiveMode::Recursive) {
+                                    eprintln!("Failed to watch {:?}: {}", root, err);
+                                    continue;
+                                }
+                                watched.insert(root.clone(), device);
+
+                                if !first_check {
+                                    log::info!("Library path {:?} is available again", root);
+                                    pending.add_changed(root.clone());
+                                    last_event = Some(Instant::now());
+                                }
+                            }
+                            // Root disappeared
+                            (Some(_), None) => {
+                                log::info!("Library path {:?} is no longer available", root);
+                                let _ = watcher.unwatch(root);
+                                watched.remove(root);
+                            }
+                            // A different file system was mounted on the root
+                            (Some(old), Some(new)) if old != new => {
+                                log::info!("Library path {:?} was remounted", root);
+                                let _ = watcher.unwatch(root);
+                                watched.remove(root);
+
+                                if let Err(err) = watcher.watch(root, RecursiveMode::Recursive) {
+                                    eprintln!("Failed to watch {:?}: {}", root, err);
+                                    continue;
+                                }
+                                watched.insert(root.clone(), new);
+                                pending.add_changed(root.clone());
+                                last_event = Some(Instant::now());
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    first_check = false;
+                }
+
+                match event_rx.recv_timeout(poll_interval) {
+                    Ok(Ok(event)) => {
+                        if Self::record_event(&mut pending, event) {
+                            last_event = Some(Instant::now());
+                        }
+                    }
+                    Ok(Err(err)) => {
+                        eprintln!("Library watcher error: {}", err);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                let Some(last) = last_event else {
+                    continue;
+                };
+
+                if last.elapsed() < debounce {
+                    continue;
+                }
+
+                last_event = None;
+
+                // Never report removals for a root that has gone away, an unmounted
+                // drive should not empty the library
+                pending
+                    .removed
+                    .retain(|path| Self::is_root_available(&roots, &watched, &mount_points, path));
+
+                if pending.is_empty() {
+                    continue;
+                }
+
+                if changes_tx.send(std::mem::take(&mut pending)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Fold a single notify event into the pending changes.
+    /// Returns `true` if the event was relevant to the library.
+    fn record_event(pending: &mut LibraryChanges, event: Event) -> bool {
+        match event.kind {
+            EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Data(_))
+            | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                for path in event.paths {
+                    pending.add_changed(path);
+                }
+                true
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let mut paths = event.paths.into_iter();
+                if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                    pending.add_removed(from);
+                    pending.add_changed(to);
+                }
+                true
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) | EventKind::Remove(_) => {
+                for path in event.paths {
+                    pending.add_removed(path);
+                }
+                true
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                // Moved in from outside the watched tree, or an unpaired rename
+                for path in event.paths {
+                    if path.exists() {
+                        pending.add_changed(path);
+                    } else {
+                        pending.add_removed(path);
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Device id of a root directory, or `None` if it is not available
+    ///
+    /// Unmounting a root that is a mount point leaves an empty directory on the
+    /// parent file system behind, so such a root only counts as available while
+    /// it is on a file system of its own.
+    fn root_device(root: &Path, mount_points: &HashSet<PathBuf>) -> Option<u64> {
+        let device = std::fs::metadata(root)
+            .ok()
+            .filter(|m| m.is_dir())
+            .map(|m| m.dev())?;
+
+        if mount_points.contains(root) && !Self::is_mount_point(root, device) {
+            return None;
+        }
+
+        Some(device)
+    }
+
+    /// Whether a root on `device` is on a different file system than its parent
+    fn is_mount_point(root: &Path, device: u64) -> bool {
+        root.parent()
+            .and_then(|parent| std::fs::metadata(parent).ok())
+            .is_some_and(|m| m.dev() != device)
+    }
+
+    /// Check that the root containing `path` is still mounted and watched
+    fn is_root_available(
+        roots: &[PathBuf],
+        watched: &HashMap<PathBuf, u64>,
+        mount_points: &HashSet<PathBuf>,
+        path: &Path,
+    ) -> bool {
+        roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .any(|root| {
+                watched
+                    .get(root)
+                    .is_some_and(|&device| Self::root_device(root, mount_points) == Some(device))
+            })
+    }
+}
@@ -0,0 +1,293 @@
+// SPDX-License-Identifier: GPL-3.0
+// src/services/loudness_service.rs
+
+use gst::prelude::*;
+use gstreamer as gst;
+use lofty::config::WriteOptions;
+use lofty::error::LoftyError;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, Tag};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// Progress updates during loudness analysis
+#[derive(Debug, Clone)]
+pub enum LoudnessProgress {
+    /// Progress update with current/total/percent
+    Progress {
+        current: f32,
+        total: f32,
+        percent: f32,
+    },
+    /// Results for every file that could be analyzed
+    Complete(Vec<(PathBuf, Loudness)>),
+    Cancelled,
+}
+
+/// ReplayGain values computed for a file
+#[derive(Debug, Clone, Default)]
+pub struct Loudness {
+    pub track_gain: f64,
+    pub track_peak: f64,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+pub struct LoudnessService;
+
+impl LoudnessService {
+    /// Analyze groups of files in a background thread
+    ///
+    /// Each group is analyzed in one pass. When `albums` is set a group is
+    /// treated as an album and every file in it also gets the album values.
+    pub fn analyze(
+        groups: Vec<Vec<PathBuf>>,
+        albums: bool,
+        progress_tx: UnboundedSender<LoudnessProgress>,
+        cancel_token: CancellationToken,
+    ) {
+        std::thread::spawn(move || {
+            let total = groups.iter().map(|g| g.len()).sum::<usize>() as f32;
+            let mut current = 0.0;
+            let mut results = Vec::new();
+
+            for group in groups {
+                let analyzed = Self::analyze_group(&group, &cancel_token, || {
+                    current += 1.0;
+                    let _ = progress_tx.send(LoudnessProgress::Progress {
+                        current,
+                        total,
+                        percent: current / total * 100.0,
+                    });
+                });
+
+                let Some(mut analyzed) = analyzed else {
+                    log::info!("Loudness analysis cancelled by user");
+                    let _ = progress_tx.send(LoudnessProgress::Cancelled);
+                    return;
+                };
+
+                if albums {
+                    // The album values come with the last track of the group
+                    let album = analyzed
+                        .iter()
+                        .find_map(|(_, l)| l.album_gain.zip(l.album_peak));
+
+                    for (_, loudness) in analyzed.iter_mut() {
+                        loudness.album_gain = album.map(|(gain, _)| gain);
+                        loudness.album_peak = album.map(|(_, peak)| peak);
+                    }
+                } else {
+                    for (_, loudness) in analyzed.iter_mut() {
+                        loudness.album_gain = None;
+                        loudness.album_peak = None;
+                    }
+                }
+
+                results.extend(analyzed);
+            }
+
+            let _ = progress_tx.send(LoudnessProgress::Complete(results));
+        });
+    }
+
+    /// Run the files through a single `rganalysis` element, one after the other
+    ///
+    /// The element keeps its album data as long as the pipeline only drops to
+    /// READY between files. Files that fail to decode are left out. Returns
+    /// `None` if cancelled.
+    fn analyze_group(
+        paths: &[PathBuf],
+        cancel_token: &CancellationToken,
+        mut on_file_done: impl FnMut(),
+    ) -> Option<Vec<(PathBuf, Loudness)>> {
+        let mut results = Vec::new();
+
+        let (pipeline, decode, sink) = match Self::build_pipeline(paths.len()) {
+            Ok(elements) => elements,
+            Err(err) => {
+                eprintln!("Failed to create loudness pipeline: {}", err);
+                paths.iter().for_each(|_| on_file_done());
+                return Some(results);
+            }
+        };
+
+        let Some(bus) = pipeline.bus() else {
+            paths.iter().for_each(|_| on_file_done());
+            return Some(results);
+        };
+
+        for path in paths {
+            let Ok(uri) = Url::from_file_path(path) else {
+                on_file_done();
+                continue;
+            };
+
+            let _ = pipeline.set_state(gst::State::Ready);
+            decode.set_property("uri", uri.as_str());
+
+            if let Err(err) = pipeline.set_state(gst::State::Playing) {
+                eprintln!("Failed to analyze {:?}: {:?}", path, err);
+                on_file_done();
+                continue;
+            }
+
+            let mut track = (None, None);
+            let mut album = (None, None);
+
+            loop {
+                if cancel_token.is_cancelled() {
+                    let _ = pipeline.set_state(gst::State::Null);
+                    return None;
+                }
+
+                let Some(msg) = bus.timed_pop(gst::ClockTime::from_mseconds(250)) else {
+                    continue;
+                };
+
+                use gst::MessageView;
+                match msg.view() {
+                    // Only the results, file tags are dropped before the analysis
+                    MessageView::Tag(tag) if msg.src() == Some(sink.upcast_ref()) => {
+                        let tags = tag.tags();
+                        if let Some(gain) = tags.get::<gst::tags::TrackGain>() {
+                            track.0 = Some(gain.get());
+                        }
+                        if let Some(peak) = tags.get::<gst::tags::TrackPeak>() {
+                            track.1 = Some(peak.get());
+                        }
+                        if let Some(gain) = tags.get::<gst::tags::AlbumGain>() {
+                            album.0 = Some(gain.get());
+                        }
+                        if let Some(peak) = tags.get::<gst::tags::AlbumPeak>() {
+                            album.1 = Some(peak.get());
+                        }
+                    }
+                    MessageView::Eos(..) => break,
+                    MessageView::Error(err) => {
+                        eprintln!("Failed to analyze {:?}: {}", path, err.error());
+                        track = (None, None);
+                        break;
+                    }
+                    _ => (),
+                }
+            }
+
+            if let (Some(track_gain), Some(track_peak)) = track {
+                results.push((
+                    path.clone(),
+                    Loudness {
+                        track_gain,
+                        track_peak,
+                        album_gain: album.0,
+                        album_peak: album.1,
+                    },
+                ));
+            }
+
+            on_file_done();
+        }
+
+        let _ = pipeline.set_state(gst::State::Null);
+
+        Some(results)
+    }
+
+    /// `uridecodebin ! audioconvert ! audioresample ! rganalysis ! fakesink`
+    fn build_pipeline(
+        num_tracks: usize,
+    ) -> Result<(gst::Pipeline, gst::Element, gst::Element), gst::glib::BoolError> {
+        let pipeline = gst::Pipeline::new();
+        let decode = gst::ElementFactory::make("uridecodebin").build()?;
+        let convert = gst::ElementFactory::make("audioconvert").build()?;
+        let resample = gst::ElementFactory::make("audioresample").build()?;
+        let analysis = gst::ElementFactory::make("rganalysis")
+            .property("num-tracks", num_tracks as i32)
+            .build()?;
+        let sink = gst::ElementFactory::make("fakesink")
+            .property("sync", false)
+            .build()?;
+
+        pipeline.add_many([&decode, &convert, &resample, &analysis, &sink])?;
+        gst::Element::link_many([&convert, &resample, &analysis, &sink])?;
+
+        // Decoded pads appear for every file, link the audio one
+        let convert_weak = convert.downgrade();
+        decode.connect_pad_added(move |_, pad| {
+            let Some(convert) = convert_weak.upgrade() else {
+                return;
+            };
+            let Some(sink_pad) = convert.static_pad("sink") else {
+                return;
+            };
+
+            let is_audio = pad
+                .current_caps()
+                .and_then(|caps| caps.structure(0).map(|s| s.name().starts_with("audio/")))
+                .unwrap_or(false);
+
+            if is_audio && !sink_pad.is_linked() {
+                let _ = pad.link(&sink_pad);
+            }
+        });
+
+        // Drop the file's own tags, existing ReplayGain tags would otherwise be
+        // mistaken for results
+        if let Some(pad) = analysis.static_pad("sink") {
+            pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, |_, info| {
+                if let Some(gst::PadProbeData::Event(ref event)) = info.data {
+                    if event.type_() == gst::EventType::Tag {
+                        return gst::PadProbeReturn::Drop;
+                    }
+                }
+
+                gst::PadProbeReturn::Ok
+            });
+        }
+
+        Ok((pipeline, decode, sink))
+    }
+
+    /// Write ReplayGain tags to the files, returns the files that failed
+    pub fn write_tags(entries: &[(PathBuf, Loudness)]) -> Vec<(PathBuf, String)> {
+        entries
+            .iter()
+            .filter_map(|(path, loudness)| {
+                Self::write_file_tags(path, loudness)
+                    .err()
+                    .map(|err| (path.clone(), err.to_string()))
+            })
+            .collect()
+    }
+
+    fn write_file_tags(path: &Path, loudness: &Loudness) -> Result<(), LoftyError> {
+        let mut tagged_file = Probe::open(path)?.read()?;
+
+        if tagged_file.primary_tag().is_none() {
+            let tag_type = tagged_file.primary_tag_type();
+            tagged_file.insert_tag(Tag::new(tag_type));
+        }
+
+        let Some(tag) = tagged_file.primary_tag_mut() else {
+            return Ok(());
+        };
+
+        tag.insert_text(
+            ItemKey::ReplayGainTrackGain,
+            format!("{:+.2} dB", loudness.track_gain),
+        );
+        tag.insert_text(
+            ItemKey::ReplayGainTrackPeak,
+            format!("{:.6}", loudness.track_peak),
+        );
+        if let (Some(gain), Some(peak)) = (loudness.album_gain, loudness.album_peak) {
+            tag.insert_text(ItemKey::ReplayGainAlbumGain, format!("{:+.2} dB", gain));
+            tag.insert_text(ItemKey::ReplayGainAlbumPeak, format!("{:.6}", peak));
+        }
+
+        tagged_file.save_to_path(path, WriteOptions::default())
+    }
+}
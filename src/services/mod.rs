@@ -1,3 +1,7 @@
 pub mod library_service;
+pub mod library_watcher;
+pub mod loudness_service;
+pub mod output_devices;
 pub mod playback_service;
+pub mod playlist_codec;
 pub mod playlist_service;
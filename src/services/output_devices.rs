@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: GPL-3.0
+// src/services/output_devices.rs
+
+use gst::prelude::*;
+use gstreamer as gst;
+
+/// An audio output as listed by GStreamer
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputDevice {
+    /// Stable name of the device, saved in the config
+    pub id: String,
+    pub name: String,
+}
+
+/// Watches the audio outputs through a `DeviceMonitor`
+pub struct OutputDevices {
+    monitor: gst::DeviceMonitor,
+    devices: Vec<OutputDevice>,
+}
+
+impl OutputDevices {
+    pub fn new() -> Self {
+        let monitor = gst::DeviceMonitor::new();
+        let _ = monitor.add_filter(Some("Audio/Sink"), None);
+
+        if let Err(err) = monitor.start() {
+            eprintln!("Failed to start device monitor: {}", err);
+        }
+
+        let mut output_devices = Self {
+            monitor,
+            devices: Vec::new(),
+        };
+        output_devices.refresh();
+
+        output_devices
+    }
+
+    pub fn devices(&self) -> &[OutputDevice] {
+        &self.devices
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.devices.iter().any(|device| device.id == id)
+    }
+
+    /// Handle added and removed devices, returns true if the list changed
+    pub fn update(&mut self) -> bool {
+        let mut changed = false;
+
+        let bus = self.monitor.bus();
+        while let Some(msg) = bus.pop() {
+            use gst::MessageView;
+            match msg.view() {
+                MessageView::DeviceAdded(..)
+                | MessageView::DeviceRemoved(..)
+                | MessageView::DeviceChanged(..) => changed = true,
+                _ => (),
+            }
+        }
+
+        if changed {
+            self.refresh();
+        }
+
+        changed
+    }
+
+    /// Create a sink element playing to the device
+    pub fn create_sink(&self, id: &str) -> Option<gst::Element> {
+        self.monitor
+            .devices()
+            .into_iter()
+            .find(|device| device_id(device) == id)
+            .and_then(|device| device.create_element(None).ok())
+    }
+
+    fn refresh(&mut self) {
+        self.devices = self
+            .monitor
+            .devices()
+            .into_iter()
+            .map(|device| OutputDevice {
+                id: device_id(&device),
+                name: device.display_name().to_string(),
+            })
+            .collect();
+    }
+}
+
+/// The most stable name a device provides, display names can repeat
+fn device_id(device: &gst::Device) -> String {
+    device
+        .properties()
+        .and_then(|properties| {
+            ["node.name", "device.name", "device.string"]
+                .iter()
+                .find_map(|field| properties.get::<String>(*field).ok())
+        })
+        .unwrap_or_else(|| device.display_name().to_string())
+}
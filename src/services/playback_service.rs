@@ -1,13 +1,26 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use crate::app::TrackId;
+use crate::constants::{
+    EQUALIZER_BAND_FREQUENCIES, EQUALIZER_RAMP_STEP, MAX_PLAYBACK_ERROR_SKIPS, MAX_PLAYBACK_RATE,
+    MIN_PLAYBACK_RATE, SLEEP_FADE_SECS,
+};
+use crate::equalizer::EqualizerGains;
 use crate::mpris::MprisCommand;
-use crate::playback_state::{PlaybackSession, PlaybackState, PlaybackStatus, RepeatMode};
-use crate::player::Player;
-use crate::playlist::Playlist;
+use crate::playback_state::{
+    PlaybackSession, PlaybackState, PlaybackStatus, RepeatMode, ReplayGainMode, ReplayGainSettings,
+    SavedEntry, SavedSession, SleepTimer,
+};
+use crate::player::{Player, PlayerError};
+use crate::playlist::{Playlist, Track};
+use crate::services::output_devices::{OutputDevice, OutputDevices};
 use gst::prelude::*;
 use gstreamer as gst;
 use rand::seq::SliceRandom;
+use std::collections::HashSet;
+use std::f64::consts::FRAC_PI_2;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::UnboundedReceiver;
 use url::Url;
 
@@ -17,13 +30,37 @@ pub enum PlaybackEvent {
     TrackEnded,
 
     GaplessTrackAdvanced,
-    Error(String),
+    CrossfadeStarted,
+    /// A track failed to play, with its file if known
+    Error(Option<PathBuf>, PlayerError),
+    OutputDevicesChanged,
+    /// Playback stopped for the sleep timer or stop after current
+    StopPointReached,
     #[allow(dead_code)]
     PositionUpdate(f32),
 }
 
+/// The track that plays after the current one
+#[derive(Debug, Clone)]
+enum UpNext {
+    /// A track from the play queue
+    Queued(Track),
+    /// An index into the session order
+    Session(usize),
+}
+
+/// A crossfade in progress, the outgoing track plays on the standby player
+#[derive(Debug, Clone, Copy)]
+struct Crossfade {
+    started: Instant,
+    duration: Duration,
+}
+
 pub struct PlaybackService {
     player: Player,
+    // Second player the outgoing track moves to during a crossfade, crossfades
+    // are off without it
+    standby: Option<Player>,
     state: PlaybackState,
     mpris_rx: UnboundedReceiver<MprisCommand>,
     // Mirrors the app-level repeat mode so gapless precomputation is correct
@@ -37,23 +74,97 @@ pub struct PlaybackService {
     // Used by advance_session_after_gapless to find the right index even if the
     // session order changes
     pending_gapless_track_id: Option<TrackId>,
+    // The queued track that was handed to GStreamer for the pending gapless
+    // transition, takes precedence over pending_gapless_track_id
+    pending_gapless_queue_track: Option<Track>,
+    // Position to seek to once a resumed track has prerolled
+    pending_resume_position: Option<f32>,
+    replay_gain: ReplayGainSettings,
+    // Crossfade length in seconds, 0 disables crossfading
+    crossfade_secs: u32,
+    crossfade: Option<Crossfade>,
+    // Equalizer gains being moved towards and the gains applied right now
+    equalizer_target: [f64; EQUALIZER_BAND_FREQUENCIES.len()],
+    equalizer_gains: [f64; EQUALIZER_BAND_FREQUENCIES.len()],
+    // Playback speed, applied with a seek as every new stream starts at 1.0
+    rate: f64,
+    rate_applied: bool,
+    // Errors from player calls, reported as events on the next tick
+    errors: Vec<(Option<PathBuf>, PlayerError)>,
+    // An error was reported for the loaded track, later ones for it are dropped
+    error_reported: bool,
+    // Failed tracks skipped since one last played
+    error_skips: u32,
+    outputs: OutputDevices,
+    // The chosen output device, and the one in use which falls back to the
+    // default (None) while the chosen one is missing
+    output_preferred: Option<String>,
+    output_active: Option<String>,
+    // Volume set by the user, the sleep timer fade-out lowers it further
+    volume: f64,
+    sleep_timer: Option<SleepTimer>,
+    sleep_timer_started: Instant,
+    sleep_fade: bool,
+    // One-shot stop once the current track ends
+    stop_after_current: bool,
+    fast_seek: bool,
+    // Position of a seek not yet reported to MPRIS
+    seeked: Option<Duration>,
+    // A-B loop points within the current track
+    loop_start: Option<gst::ClockTime>,
+    loop_end: Option<gst::ClockTime>,
 }
 
 impl PlaybackService {
-    pub fn new(mpris_rx: UnboundedReceiver<MprisCommand>) -> Self {
-        Self {
-            player: Player::new(),
+    pub fn new(mpris_rx: UnboundedReceiver<MprisCommand>) -> Result<Self, PlayerError> {
+        let player = Player::new()?;
+        let standby = Player::new()
+            .inspect_err(|err| eprintln!("Failed to create the crossfade player: {}", err))
+            .ok();
+
+        Ok(Self {
+            player,
+            standby,
             state: PlaybackState::new(),
             mpris_rx,
             repeat_mode: RepeatMode::All,
             repeat_enabled: false,
             gapless_pending: false,
             pending_gapless_track_id: None,
-        }
+            pending_gapless_queue_track: None,
+            pending_resume_position: None,
+            replay_gain: ReplayGainSettings::default(),
+            crossfade_secs: 0,
+            crossfade: None,
+            equalizer_target: [0.0; EQUALIZER_BAND_FREQUENCIES.len()],
+            equalizer_gains: [0.0; EQUALIZER_BAND_FREQUENCIES.len()],
+            rate: 1.0,
+            rate_applied: true,
+            errors: Vec::new(),
+            error_reported: false,
+            error_skips: 0,
+            outputs: OutputDevices::new(),
+            output_preferred: None,
+            output_active: None,
+            volume: 1.0,
+            sleep_timer: None,
+            sleep_timer_started: Instant::now(),
+            sleep_fade: false,
+            stop_after_current: false,
+            fast_seek: false,
+            seeked: None,
+            loop_start: None,
+            loop_end: None,
+        })
     }
 
     // ===== State Access =====
 
+    /// GStreamer elements that aren't installed, the features using them are off
+    pub fn missing_elements(&self) -> &[String] {
+        self.player.missing_elements()
+    }
+
     pub fn status(&self) -> PlaybackStatus {
         self.state.status
     }
@@ -70,6 +181,21 @@ impl PlaybackService {
         self.state.session.as_ref()
     }
 
+    /// The track that is playing, either from the queue or the session
+    pub fn current_track(&self) -> Option<&Track> {
+        self.state.queue_current.as_ref().or_else(|| {
+            self.state
+                .session
+                .as_ref()
+                .and_then(|s| s.order.get(s.index))
+        })
+    }
+
+    /// Whether the current track was started from the queue
+    pub fn is_playing_from_queue(&self) -> bool {
+        self.state.queue_current.is_some()
+    }
+
     pub fn set_dragging_slider(&mut self, dragging: bool) {
         self.state.dragging_slider = dragging;
     }
@@ -91,23 +217,50 @@ impl PlaybackService {
     // ===== Playback Control =====
 
     pub fn play(&mut self) {
-        self.player.play();
-        self.state.status = PlaybackStatus::Playing;
+        let result = self.player.play();
+        self.state.status = match result {
+            Ok(()) => PlaybackStatus::Playing,
+            Err(_) => PlaybackStatus::Stopped,
+        };
+        self.report(result);
     }
 
     pub fn pause(&mut self) {
-        self.player.pause();
+        self.finish_crossfade();
+        let result = self.player.pause();
+        self.report(result);
         self.state.status = PlaybackStatus::Paused;
     }
 
     pub fn stop(&mut self) {
-        self.player.stop();
+        self.finish_crossfade();
+        let result = self.player.stop();
+        self.report(result);
         self.player.set_queued_uri(None);
         self.gapless_pending = false;
         self.pending_gapless_track_id = None;
+        self.pending_gapless_queue_track = None;
         self.state.status = PlaybackStatus::Stopped;
     }
 
+    /// Move past a track that failed to play
+    ///
+    /// Stops instead once `MAX_PLAYBACK_ERROR_SKIPS` tracks failed in a row.
+    /// Returns whether the track was skipped.
+    pub fn skip_failed_track(&mut self, repeat_mode: RepeatMode, repeat: bool) -> bool {
+        self.error_skips += 1;
+
+        if self.error_skips > MAX_PLAYBACK_ERROR_SKIPS {
+            self.stop();
+            // Don't start over on errors from stopping
+            self.errors.clear();
+            return false;
+        }
+
+        self.next(repeat_mode, repeat);
+        true
+    }
+
     pub fn play_pause(&mut self) {
         match self.state.status {
             PlaybackStatus::Stopped | PlaybackStatus::Paused => self.play(),
@@ -116,22 +269,269 @@ impl PlaybackService {
     }
 
     pub fn set_volume(&mut self, volume: f64) {
-        self.player.set_volume(volume);
+        self.volume = volume;
+        self.apply_volume();
+    }
+
+    /// Set the equalizer band gains, `None` bypasses the equalizer
+    ///
+    /// The gains move to the new values over a few ticks to avoid clicks.
+    pub fn set_equalizer(&mut self, gains: Option<&EqualizerGains>) {
+        self.equalizer_target = match gains {
+            Some(gains) => gains.map(|gain| gain as f64),
+            None => [0.0; EQUALIZER_BAND_FREQUENCIES.len()],
+        };
+    }
+
+    /// Audio outputs that can be chosen
+    pub fn output_devices(&self) -> &[OutputDevice] {
+        self.outputs.devices()
+    }
+
+    /// Play to the output device with the given id, `None` for the default
+    pub fn set_output_device(&mut self, id: Option<String>) {
+        self.output_preferred = id;
+        self.apply_output_device();
+    }
+
+    /// Set the crossfade length in seconds, 0 turns crossfading off
+    pub fn set_crossfade(&mut self, seconds: u32) {
+        if self.crossfade_secs == seconds {
+            return;
+        }
+        self.crossfade_secs = seconds;
+        // Crossfaded transitions don't use the gapless hand-off
+        self.queue_next_uri();
     }
 
+    /// Change how ReplayGain is applied, takes effect on the current track
+    pub fn set_replay_gain(&mut self, settings: ReplayGainSettings) {
+        if self.replay_gain == settings {
+            return;
+        }
+        self.replay_gain = settings;
+
+        let gain = self
+            .current_track()
+            .map(|t| self.replay_gain_volume(t))
+            .unwrap_or(1.0);
+        self.player.set_replay_gain(gain);
+        self.queue_next_uri();
+    }
+
+    /// Seek to a time in seconds
     pub fn seek(&mut self, time: f32) {
-        if let Err(err) = self.player.playbin.seek_simple(
-            gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
-            gst::ClockTime::from_seconds(time as u64),
+        self.seek_position(Duration::from_secs_f32(time.max(0.0)));
+    }
+
+    /// Seek to an exact position, or the nearest keyframe with fast seeking
+    pub fn seek_position(&mut self, position: Duration) {
+        self.finish_crossfade();
+
+        let flags = if self.fast_seek {
+            gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT | gst::SeekFlags::SNAP_NEAREST
+        } else {
+            gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE
+        };
+        self.jump_to(
+            gst::ClockTime::from_nseconds(position.as_nanos() as u64),
+            flags,
+        );
+    }
+
+    /// Position of the last seek or jump since the previous call
+    pub fn take_seeked(&mut self) -> Option<Duration> {
+        self.seeked.take()
+    }
+
+    /// Seek to keyframes, faster but can land seconds off on some files
+    pub fn set_fast_seek(&mut self, fast_seek: bool) {
+        self.fast_seek = fast_seek;
+    }
+
+    /// Position in the current track, more precise than `progress`
+    pub fn position(&self) -> Duration {
+        self.player
+            .playbin
+            .query_position::<gst::ClockTime>()
+            .map(|position| Duration::from_nanos(position.nseconds()))
+            .unwrap_or_else(|| Duration::from_secs_f32(self.state.progress.max(0.0)))
+    }
+
+    /// Length of the current track, once the stream knows it
+    pub fn duration(&self) -> Option<Duration> {
+        self.player
+            .playbin
+            .query_duration::<gst::ClockTime>()
+            .map(|duration| Duration::from_nanos(duration.nseconds()))
+    }
+
+    /// Playback speed, 1.0 is normal
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Set the playback speed, scaletempo keeps the pitch
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate.clamp(MIN_PLAYBACK_RATE, MAX_PLAYBACK_RATE);
+        if self.state.status != PlaybackStatus::Stopped {
+            self.apply_rate();
+        }
+    }
+
+    /// Seek in place to switch the current stream to the playback rate
+    fn apply_rate(&mut self) {
+        if let Some(position) = self.player.playbin.query_position::<gst::ClockTime>() {
+            self.seek_to(position, gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE);
+        }
+    }
+
+    /// Seek the current stream, every seek also sets the playback rate
+    ///
+    /// With an A-B loop the seek becomes a segment seek that stops at B, a
+    /// position past B goes back to A. Returns the position seeked to.
+    fn seek_to(
+        &mut self,
+        position: gst::ClockTime,
+        flags: gst::SeekFlags,
+    ) -> Option<gst::ClockTime> {
+        let (position, flags, stop_type, stop) = match self.loop_points() {
+            Some((start, end)) => (
+                if position >= end { start } else { position },
+                flags | gst::SeekFlags::SEGMENT,
+                gst::SeekType::Set,
+                Some(end),
+            ),
+            None => (position, flags, gst::SeekType::None, gst::ClockTime::NONE),
+        };
+
+        match self.player.playbin.seek(
+            self.rate,
+            flags,
+            gst::SeekType::Set,
+            position,
+            stop_type,
+            stop,
         ) {
-            eprintln!("Failed to seek: {:?}", err);
+            Ok(()) => {
+                self.rate_applied = true;
+                Some(position)
+            }
+            Err(err) => {
+                eprintln!("Failed to seek: {:?}", err);
+                None
+            }
         }
     }
 
+    /// Seek away from the current position, reported through `take_seeked`
+    fn jump_to(&mut self, position: gst::ClockTime, flags: gst::SeekFlags) {
+        if let Some(position) = self.seek_to(position, flags) {
+            self.seeked = Some(Duration::from_nanos(position.nseconds()));
+        }
+    }
+
+    // ===== A-B Loop =====
+
+    /// Loop start and end in seconds
+    pub fn ab_loop(&self) -> (Option<f32>, Option<f32>) {
+        let seconds = |time: gst::ClockTime| time.mseconds() as f32 / 1000.0;
+        (self.loop_start.map(seconds), self.loop_end.map(seconds))
+    }
+
+    /// Set A at the current position, B is dropped if it isn't after A
+    pub fn set_loop_start(&mut self) {
+        let Some(position) = self.player.playbin.query_position::<gst::ClockTime>() else {
+            return;
+        };
+
+        self.loop_start = Some(position);
+        if self.loop_end.is_some_and(|end| end <= position) {
+            self.loop_end = None;
+            self.seek_to(position, gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE);
+        }
+    }
+
+    /// Set B at the current position and start looping, A defaults to the
+    /// start of the track
+    pub fn set_loop_end(&mut self) {
+        let Some(position) = self.player.playbin.query_position::<gst::ClockTime>() else {
+            return;
+        };
+
+        let start = self.loop_start.unwrap_or(gst::ClockTime::ZERO);
+        if position <= start {
+            return;
+        }
+
+        self.loop_start = Some(start);
+        self.loop_end = Some(position);
+        // Back to A right away, the position is at B
+        self.jump_to(start, gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE);
+    }
+
+    pub fn clear_loop(&mut self) {
+        let looping = self.loop_points().is_some();
+        self.loop_start = None;
+        self.loop_end = None;
+
+        // Drop the segment stop at B
+        if looping {
+            if let Some(position) = self.player.playbin.query_position::<gst::ClockTime>() {
+                self.seek_to(position, gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE);
+            }
+        }
+    }
+
+    fn loop_points(&self) -> Option<(gst::ClockTime, gst::ClockTime)> {
+        self.loop_start.zip(self.loop_end)
+    }
+
+    // ===== Sleep Timer =====
+
+    pub fn sleep_timer(&self) -> Option<SleepTimer> {
+        self.sleep_timer
+    }
+
+    /// Start, restart or cancel (`None`) the sleep timer
+    pub fn set_sleep_timer(&mut self, timer: Option<SleepTimer>) {
+        self.sleep_timer = timer;
+        self.sleep_timer_started = Instant::now();
+        self.apply_volume();
+        self.queue_next_uri();
+    }
+
+    /// Fade out over the last seconds before the sleep timer stops playback
+    pub fn set_sleep_fade(&mut self, fade: bool) {
+        self.sleep_fade = fade;
+        self.apply_volume();
+    }
+
+    /// Time left on a sleep timer set in minutes
+    pub fn sleep_timer_remaining(&self) -> Option<Duration> {
+        match self.sleep_timer? {
+            SleepTimer::Minutes(minutes) => Some(
+                Duration::from_secs(minutes as u64 * 60)
+                    .saturating_sub(self.sleep_timer_started.elapsed()),
+            ),
+            SleepTimer::EndOfTrack | SleepTimer::EndOfAlbum => None,
+        }
+    }
+
+    pub fn stop_after_current(&self) -> bool {
+        self.stop_after_current
+    }
+
+    pub fn set_stop_after_current(&mut self, stop: bool) {
+        self.stop_after_current = stop;
+        self.queue_next_uri();
+    }
+
     // ===== Session Management =====
 
     /// Start a new playback session from a playlist
     pub fn start_session(&mut self, playlist: &Playlist, index: usize, shuffle: bool) {
+        self.error_skips = 0;
         let mut order = playlist.tracks().to_vec();
 
         let actual_index = if shuffle {
@@ -161,12 +561,126 @@ impl PlaybackService {
             index: actual_index,
         });
 
+        // Playing a track directly interrupts the queue but keeps its entries
+        self.state.queue_current = None;
         self.gapless_pending = false;
         self.pending_gapless_track_id = None;
+        self.pending_gapless_queue_track = None;
         self.update_now_playing();
         self.load_current_track();
     }
 
+    /// Snapshot of the session for resuming it on the next launch
+    pub fn saved_session(&self) -> Option<SavedSession> {
+        let session = self.state.session.as_ref()?;
+
+        let order = session
+            .order
+            .iter()
+            .map(|t| SavedEntry {
+                entry_id: t.entry_id,
+                track_id: t.metadata.id.clone(),
+            })
+            .collect();
+
+        Some(SavedSession {
+            playlist_id: session.playlist_id,
+            order,
+            index: session.index,
+        })
+    }
+
+    /// Position in the session track for resuming it on the next launch
+    pub fn saved_position(&self) -> f32 {
+        // A queued track isn't part of the session, resume from the start of
+        // the session track instead
+        if self.state.session.is_none() || self.is_playing_from_queue() {
+            0.0
+        } else {
+            self.state.progress
+        }
+    }
+
+    /// Restore a saved session, paused at the saved position
+    ///
+    /// Entries that no longer exist in the playlist are dropped and tracks
+    /// added since are appended. If the current track is gone playback resumes
+    /// from the start of the next remaining one, or the last remaining one
+    /// before it when there is none. Returns false if nothing could be restored.
+    pub fn restore_session(
+        &mut self,
+        playlist: &Playlist,
+        saved: &SavedSession,
+        position: f32,
+    ) -> bool {
+        let mut used: HashSet<usize> = HashSet::new();
+        let mut order = Vec::with_capacity(playlist.len());
+        let mut index = None;
+        let mut previous = None;
+        let mut position = position;
+
+        for (i, entry) in saved.order.iter().enumerate() {
+            let found = playlist
+                .tracks()
+                .iter()
+                .enumerate()
+                .find(|(j, t)| !used.contains(j) && t.entry_id == entry.entry_id)
+                .or_else(|| {
+                    playlist.tracks().iter().enumerate().find(|(j, t)| {
+                        !used.contains(j)
+                            && entry.track_id.is_some()
+                            && t.metadata.id == entry.track_id
+                    })
+                });
+
+            match found {
+                Some((j, track)) if !track.missing => {
+                    used.insert(j);
+                    if i < saved.index {
+                        previous = Some(order.len());
+                    } else if index.is_none() {
+                        index = Some(order.len());
+                        if i != saved.index {
+                            position = 0.0;
+                        }
+                    }
+                    order.push(track.clone());
+                }
+                _ => {}
+            }
+        }
+
+        for (j, track) in playlist.tracks().iter().enumerate() {
+            if !used.contains(&j) && !track.missing {
+                order.push(track.clone());
+            }
+        }
+
+        if order.is_empty() {
+            return false;
+        }
+
+        let index = index.unwrap_or_else(|| {
+            position = 0.0;
+            previous.unwrap_or(0)
+        });
+
+        self.state.session = Some(PlaybackSession {
+            playlist_id: playlist.id(),
+            order,
+            index,
+        });
+
+        self.update_now_playing();
+        self.load_current_track();
+        self.pause();
+
+        self.state.progress = position;
+        self.pending_resume_position = (position > 0.0).then_some(position);
+
+        true
+    }
+
     /// Update shuffle setting for current session
     pub fn update_session_shuffle(&mut self, playlist: &Playlist, shuffle: bool) -> bool {
         let Some(session) = &self.state.session else {
@@ -177,7 +691,7 @@ impl PlaybackService {
             return false;
         }
 
-        let current_track_id = self.get_current_track_id();
+        let current_track_id = self.get_session_track_id();
         let mut new_order = playlist.tracks().to_vec();
 
         if shuffle {
@@ -212,7 +726,7 @@ impl PlaybackService {
 
     /// Update session when library is modified
     pub fn update_session_for_library(&mut self, library: &Playlist) -> bool {
-        let current_track_id = self.get_current_track_id();
+        let current_track_id = self.get_session_track_id();
 
         let Some(session) = &mut self.state.session else {
             return false;
@@ -250,8 +764,9 @@ impl PlaybackService {
             None
         };
 
-        // If currently playing track was removed, stop playback
-        if new_index.is_none() && current_track_id.is_some() {
+        // If currently playing track was removed, stop playback. A track playing
+        // from the queue carries on and the session picks up from the start.
+        if new_index.is_none() && current_track_id.is_some() && !self.is_playing_from_queue() {
             self.stop();
             self.state.session = None;
             self.state.now_playing = None;
@@ -295,6 +810,69 @@ impl PlaybackService {
         true
     }
 
+    /// Insert a track into the session order after an entry, first for `None`
+    ///
+    /// The track gets its own entry id, which is returned.
+    pub fn insert_session_track(&mut self, track: Track, after: Option<u32>) -> Option<u32> {
+        let session = self.state.session.as_mut()?;
+
+        let position = match after {
+            Some(entry_id) => session.order.iter().position(|t| t.entry_id == entry_id)? + 1,
+            None => 0,
+        };
+
+        let track = Self::queue_entry(track);
+        let entry_id = track.entry_id;
+        session.order.insert(position, track);
+        if session.order.len() > 1 && position <= session.index {
+            session.index += 1;
+        }
+
+        self.queue_next_uri();
+
+        Some(entry_id)
+    }
+
+    /// Remove a track from the session order, except the current one
+    pub fn remove_session_track(&mut self, entry_id: u32) -> bool {
+        let Some(session) = &mut self.state.session else {
+            return false;
+        };
+
+        match session.order.iter().position(|t| t.entry_id == entry_id) {
+            Some(position) if position != session.index => {
+                session.order.remove(position);
+                if position < session.index {
+                    session.index -= 1;
+                }
+            }
+            _ => return false,
+        }
+
+        self.queue_next_uri();
+
+        true
+    }
+
+    /// Play the session track with the entry id
+    pub fn go_to_session_track(&mut self, entry_id: u32) -> bool {
+        let Some(session) = &mut self.state.session else {
+            return false;
+        };
+        let Some(index) = session.order.iter().position(|t| t.entry_id == entry_id) else {
+            return false;
+        };
+
+        session.index = index;
+        self.state.queue_current = None;
+        self.error_skips = 0;
+        self.load_current_track();
+        self.play();
+        self.update_now_playing();
+
+        true
+    }
+
     // ===== Navigation =====
 
     pub fn next(&mut self, repeat_mode: RepeatMode, repeat_enabled: bool) {
@@ -302,29 +880,41 @@ impl PlaybackService {
         self.repeat_mode = repeat_mode.clone();
         self.repeat_enabled = repeat_enabled;
 
+        if repeat_mode == RepeatMode::One && self.current_track().is_some() {
+            // Restart current track
+            self.load_current_track();
+            self.play();
+            return;
+        }
+
+        // The queue takes priority over the session order
+        if !self.state.queue.is_empty() {
+            self.state.queue_current = Some(self.state.queue.remove(0));
+            self.load_current_track();
+            self.play();
+            self.update_now_playing();
+            return;
+        }
+
+        let from_queue = self.state.queue_current.take().is_some();
+
         let Some(session) = &mut self.state.session else {
+            if from_queue {
+                self.stop();
+                self.state.now_playing = None;
+            }
             return;
         };
 
-        match repeat_mode {
-            RepeatMode::One => {
-                // Restart current track
-                self.load_current_track();
-                self.play();
-                return;
-            }
-            RepeatMode::All => {
-                if session.index + 1 < session.order.len() {
-                    session.index += 1;
-                } else if repeat_enabled {
-                    // Only wrap to beginning if repeat is enabled
-                    session.index = 0;
-                } else {
-                    // Reached end without repeat - stop playback
-                    self.stop();
-                    return;
-                }
-            }
+        if session.index + 1 < session.order.len() {
+            session.index += 1;
+        } else if repeat_enabled {
+            // Only wrap to beginning if repeat is enabled
+            session.index = 0;
+        } else {
+            // Reached end without repeat - stop playback
+            self.stop();
+            return;
         }
 
         self.load_current_track();
@@ -332,7 +922,37 @@ impl PlaybackService {
         self.update_now_playing();
     }
 
+    /// Whether `next` has a track to move to
+    pub fn has_next(&self) -> bool {
+        if self.repeat_mode == RepeatMode::One && self.current_track().is_some() {
+            return true;
+        }
+
+        !self.state.queue.is_empty()
+            || self.state.session.as_ref().is_some_and(|session| {
+                session.index + 1 < session.order.len()
+                    || (self.repeat_enabled && !session.order.is_empty())
+            })
+    }
+
+    /// Whether `prev` has a track to move to
+    pub fn has_previous(&self) -> bool {
+        self.state
+            .session
+            .as_ref()
+            .is_some_and(|session| !session.order.is_empty())
+    }
+
     pub fn prev(&mut self, repeat_mode: RepeatMode) {
+        // Going back from a queued track returns to the session track that
+        // played before the queue
+        if self.state.queue_current.take().is_some() && self.state.session.is_some() {
+            self.load_current_track();
+            self.play();
+            self.update_now_playing();
+            return;
+        }
+
         let Some(session) = &mut self.state.session else {
             return;
         };
@@ -359,10 +979,91 @@ impl PlaybackService {
         self.update_now_playing();
     }
 
+    // ===== Queue =====
+
+    pub fn queue(&self) -> &[Track] {
+        &self.state.queue
+    }
+
+    /// Insert tracks right after the current track, keeping their order
+    pub fn play_next(&mut self, tracks: Vec<Track>) {
+        let tracks = tracks.into_iter().map(Self::queue_entry);
+        self.state.queue.splice(0..0, tracks);
+        self.queue_next_uri();
+    }
+
+    /// Append tracks to the end of the queue
+    pub fn enqueue(&mut self, tracks: Vec<Track>) {
+        let tracks = tracks.into_iter().map(Self::queue_entry);
+        self.state.queue.extend(tracks);
+        self.queue_next_uri();
+    }
+
+    /// Move a queue entry from one position to another
+    pub fn move_queue_entry(&mut self, from: usize, to: usize) {
+        let queue = &mut self.state.queue;
+        if from >= queue.len() || to >= queue.len() {
+            return;
+        }
+
+        let track = queue.remove(from);
+        queue.insert(to, track);
+        self.queue_next_uri();
+    }
+
+    pub fn remove_queue_entry(&mut self, index: usize) {
+        if index < self.state.queue.len() {
+            self.state.queue.remove(index);
+            self.queue_next_uri();
+        }
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.state.queue.clear();
+        self.queue_next_uri();
+    }
+
+    /// Queue entries get their own entry id so the same track can be queued
+    /// more than once
+    fn queue_entry(mut track: Track) -> Track {
+        track.generate_entry_id();
+        track.selected = false;
+        track
+    }
+
     /// Process one tick cycle - handles GStreamer messages and MPRIS commands
     /// Returns events that the app should handle
     pub fn tick(&mut self) -> Vec<PlaybackEvent> {
-        let mut events = Vec::new();
+        let errors: Vec<_> = self.errors.drain(..).collect();
+        let mut events: Vec<PlaybackEvent> = errors
+            .into_iter()
+            .filter_map(|(path, err)| self.error_event(path, err))
+            .collect();
+
+        self.update_equalizer();
+
+        // A sleep timer set in minutes ran out
+        if self.sleep_timer_remaining() == Some(Duration::ZERO) {
+            if self.state.status == PlaybackStatus::Playing {
+                self.stop();
+            }
+            self.sleep_timer = None;
+            self.apply_volume();
+            events.push(PlaybackEvent::StopPointReached);
+        } else if self.sleep_timer.is_some() && self.sleep_fade {
+            self.apply_volume();
+        }
+
+        if self.outputs.update() {
+            self.apply_output_device();
+            events.push(PlaybackEvent::OutputDevicesChanged);
+        }
+
+        if self.crossfade.is_some() {
+            self.update_crossfade();
+        } else if self.start_crossfade() {
+            events.push(PlaybackEvent::CrossfadeStarted);
+        }
 
         // Poll whether the about-to-finish callback fired on GStreamer's thread
         if self.player.take_about_to_finish() {
@@ -380,7 +1081,12 @@ impl PlaybackService {
                         // with no repeat, or a non-gapless stop), clear any stale flag
                         //eprintln!("[gapless] EOS received (no queued URI)");
                         self.gapless_pending = false;
-                        events.push(PlaybackEvent::TrackEnded);
+                        if self.stops_after_current() {
+                            self.reach_stop_point();
+                            events.push(PlaybackEvent::StopPointReached);
+                        } else {
+                            events.push(PlaybackEvent::TrackEnded);
+                        }
                     }
                     MessageView::StreamStart(..) => {
                         // A STREAM_START following an about-to-finish notification means
@@ -388,24 +1094,59 @@ impl PlaybackService {
                         if self.gapless_pending {
                             //eprintln!("[gapless] STREAM_START - gapless transition confirmed");
                             self.gapless_pending = false;
+                            // The stop point was set after the next track was
+                            // handed over, stop right as it starts
+                            let stop = self.stops_after_current();
                             self.advance_session_after_gapless();
                             events.push(PlaybackEvent::GaplessTrackAdvanced);
+                            if stop {
+                                self.reach_stop_point();
+                                events.push(PlaybackEvent::StopPointReached);
+                            } else if self.rate != 1.0 {
+                                self.apply_rate();
+                            }
                         } else {
                             //eprintln!("[gapless] STREAM_START - normal track load");
                         }
                     }
+                    MessageView::AsyncDone(..) => {
+                        // The track prerolled fine
+                        self.error_skips = 0;
+
+                        // A resumed track can only be seeked once it has prerolled
+                        if let Some(position) = self.pending_resume_position.take() {
+                            self.seek(position);
+                        } else if !self.rate_applied {
+                            self.apply_rate();
+                        }
+                    }
+                    MessageView::SegmentDone(..) => {
+                        // Reached B, a non-flushing seek keeps the loop seamless
+                        if let Some((start, _)) = self.loop_points() {
+                            self.jump_to(start, gst::SeekFlags::ACCURATE);
+                        }
+                    }
+                    MessageView::ClockLost(..) => {
+                        // The output changed, restart to pick up a new clock
+                        if self.state.status == PlaybackStatus::Playing {
+                            let result = self.player.pause().and_then(|_| self.player.play());
+                            self.report(result);
+                        }
+                    }
                     MessageView::Error(err) => {
                         eprintln!("GStreamer error: {}", err.error());
                         self.gapless_pending = false;
-                        events.push(PlaybackEvent::Error(err.error().to_string()));
+                        let path = self.current_track().map(|t| t.path.clone());
+                        let err = PlayerError::Stream(err.error().to_string());
+                        events.extend(self.error_event(path, err));
                     }
                     _ => (),
                 }
             }
         }
 
-        // Update position if not dragging slider
-        if !self.state.dragging_slider {
+        // Update position if not dragging slider or waiting to resume
+        if !self.state.dragging_slider && self.pending_resume_position.is_none() {
             if let Some(pos) = self.player.playbin.query_position::<gst::ClockTime>() {
                 self.state.progress = pos.mseconds() as f32 / 1000.0;
                 events.push(PlaybackEvent::PositionUpdate(self.state.progress));
@@ -429,12 +1170,19 @@ impl PlaybackService {
     // ===== Private Helpers =====
 
     fn load_current_track(&mut self) {
-        if let Some(session) = &self.state.session {
-            if let Some(track) = session.order.get(session.index) {
-                if let Ok(url) = Url::from_file_path(&track.path) {
-                    self.player.stop();
-                    self.player.load(url.as_str());
-                }
+        self.error_reported = false;
+        self.pending_resume_position = None;
+        self.loop_start = None;
+        self.loop_end = None;
+        self.rate_applied = self.rate == 1.0;
+        self.finish_crossfade();
+        if let Some(track) = self.current_track() {
+            if let Ok(url) = Url::from_file_path(&track.path) {
+                let gain = self.replay_gain_volume(track);
+                let result = self.player.stop();
+                self.player.load(url.as_str());
+                self.player.set_replay_gain(gain);
+                self.report(result);
             }
         }
         // Pre-queue next track so about-to-finish has it ready
@@ -442,16 +1190,10 @@ impl PlaybackService {
     }
 
     fn update_now_playing(&mut self) {
-        if let Some(session) = &self.state.session {
-            if let Some(track) = session.order.get(session.index) {
-                self.state.now_playing = Some(track.metadata.clone());
-                return;
-            }
-        }
-        self.state.now_playing = None;
+        self.state.now_playing = self.current_track().map(|t| t.metadata.clone());
     }
 
-    fn get_current_track_id(&self) -> Option<String> {
+    fn get_session_track_id(&self) -> Option<String> {
         self.state
             .session
             .as_ref()
@@ -459,6 +1201,22 @@ impl PlaybackService {
             .and_then(|t| t.metadata.id.clone())
     }
 
+    /// Compute what plays next without mutating state
+    /// Returns None if there is no next track (end of playlist, no repeat)
+    fn compute_next(&self) -> Option<UpNext> {
+        if self.repeat_mode == RepeatMode::One {
+            // Repeat the same track
+            if let Some(track) = &self.state.queue_current {
+                return Some(UpNext::Queued(track.clone()));
+            }
+        } else if let Some(track) = self.state.queue.first() {
+            // The queue takes priority over the session order
+            return Some(UpNext::Queued(track.clone()));
+        }
+
+        self.compute_next_index().map(UpNext::Session)
+    }
+
     /// Compute what the next session index should be without mutating state
     /// Returns None if there is no next track (end of playlist, no repeat)
     fn compute_next_index(&self) -> Option<usize> {
@@ -487,41 +1245,394 @@ impl PlaybackService {
     /// Pre-queue the next track URI in the Player so GStreamer can transition
     /// gaplessly when about-to-finish fires
     fn queue_next_uri(&mut self) {
-        let next = self.compute_next_index().and_then(|idx| {
-            self.state
-                .session
-                .as_ref()
-                .and_then(|s| s.order.get(idx))
-                .and_then(|t| {
-                    Url::from_file_path(&t.path)
-                        .ok()
-                        .map(|u| (u.to_string(), t.metadata.id.clone()))
-                })
+        let next = self.compute_next().and_then(|up_next| {
+            let track = match &up_next {
+                UpNext::Queued(track) => Some(track),
+                UpNext::Session(idx) => self.state.session.as_ref().and_then(|s| s.order.get(*idx)),
+            }?;
+
+            let uri = Url::from_file_path(&track.path).ok()?.to_string();
+            let track_id = track.metadata.id.clone();
+            let gain = self.replay_gain_volume(track);
+            let queue_track = match up_next {
+                UpNext::Queued(track) => Some(track),
+                UpNext::Session(_) => None,
+            };
+
+            Some((uri, track_id, gain, queue_track))
         });
 
+        // Nothing plays past a stop point, and a crossfade takes over from the
+        // gapless hand-off for this transition
+        let next = next.filter(|_| !self.stops_after_current() && !self.crossfade_to_next());
+
         match next {
-            Some((uri, track_id)) => {
+            Some((uri, track_id, gain, queue_track)) => {
                 // Only update if the pending ID if not in mid-transition
                 if !self.gapless_pending {
                     self.pending_gapless_track_id = track_id;
+                    self.pending_gapless_queue_track = queue_track;
                 }
+                self.player.set_queued_gain(gain);
                 self.player.set_queued_uri(Some(uri));
             }
             None => {
                 if !self.gapless_pending {
                     self.pending_gapless_track_id = None;
+                    self.pending_gapless_queue_track = None;
                 }
                 self.player.set_queued_uri(None)
             }
         }
     }
 
+    /// The track an `UpNext` refers to
+    fn up_next_track<'a>(&'a self, up_next: &'a UpNext) -> Option<&'a Track> {
+        match up_next {
+            UpNext::Queued(track) => Some(track),
+            UpNext::Session(idx) => self.state.session.as_ref().and_then(|s| s.order.get(*idx)),
+        }
+    }
+
+    /// Whether the transition to the next track should crossfade
+    ///
+    /// Consecutive tracks of the same album are left gapless.
+    fn crossfade_to_next(&self) -> bool {
+        if self.crossfade_secs == 0
+            || !self.can_crossfade()
+            || self.loop_points().is_some()
+            || self.stops_after_current()
+        {
+            return false;
+        }
+
+        let Some(current) = self.current_track() else {
+            return false;
+        };
+        let Some(up_next) = self.compute_next() else {
+            return false;
+        };
+        let Some(next) = self.up_next_track(&up_next) else {
+            return false;
+        };
+
+        // Repeating a single track
+        if current.path == next.path {
+            return false;
+        }
+
+        !same_album(current, next)
+    }
+
+    /// Whether playback stops once the current track ends
+    fn stops_after_current(&self) -> bool {
+        if self.stop_after_current {
+            return true;
+        }
+
+        match self.sleep_timer {
+            Some(SleepTimer::EndOfTrack) => true,
+            Some(SleepTimer::EndOfAlbum) => {
+                let up_next = self.compute_next();
+                let next = up_next.as_ref().and_then(|n| self.up_next_track(n));
+                match (self.current_track(), next) {
+                    (Some(current), Some(next)) => !same_album(current, next),
+                    _ => true,
+                }
+            }
+            Some(SleepTimer::Minutes(_)) | None => false,
+        }
+    }
+
+    /// Stop for stop after current or a sleep timer waiting for the track end
+    fn reach_stop_point(&mut self) {
+        self.stop();
+        self.stop_after_current = false;
+        if matches!(
+            self.sleep_timer,
+            Some(SleepTimer::EndOfTrack | SleepTimer::EndOfAlbum)
+        ) {
+            self.sleep_timer = None;
+        }
+        self.apply_volume();
+    }
+
+    /// Time until the current track ends, at the playback rate
+    fn track_time_left(&self) -> Option<Duration> {
+        let position = self.player.playbin.query_position::<gst::ClockTime>()?;
+        let duration = self.player.playbin.query_duration::<gst::ClockTime>()?;
+
+        Some(Duration::from_nanos(duration.saturating_sub(position).nseconds()).div_f64(self.rate))
+    }
+
+    /// Apply the user volume, lowered while the sleep timer fades out
+    fn apply_volume(&mut self) {
+        let mut volume = self.volume;
+
+        let time_left = match self.sleep_timer {
+            Some(SleepTimer::Minutes(_)) => self.sleep_timer_remaining(),
+            Some(_) if self.stops_after_current() => self.track_time_left(),
+            _ => None,
+        };
+
+        let fade = Duration::from_secs(SLEEP_FADE_SECS);
+        if let Some(time_left) = time_left.filter(|left| self.sleep_fade && *left < fade) {
+            volume *= time_left.as_secs_f64() / fade.as_secs_f64();
+        }
+
+        self.player.set_volume(volume);
+        if let Some(standby) = &mut self.standby {
+            standby.set_volume(volume);
+        }
+    }
+
+    /// Start the next track on a fresh player once the current one is within
+    /// the crossfade length of its end. Returns true if a crossfade started.
+    fn start_crossfade(&mut self) -> bool {
+        if self.state.status != PlaybackStatus::Playing
+            || self.state.dragging_slider
+            || self.gapless_pending
+            || !self.crossfade_to_next()
+        {
+            return false;
+        }
+
+        let Some(remaining) = self.track_time_left() else {
+            return false;
+        };
+        if remaining > Duration::from_secs(self.crossfade_secs as u64) {
+            return false;
+        }
+
+        let Some(up_next) = self.compute_next() else {
+            return false;
+        };
+        let Some((url, gain)) = self.up_next_track(&up_next).and_then(|track| {
+            let url = Url::from_file_path(&track.path).ok()?;
+            Some((url, self.replay_gain_volume(track)))
+        }) else {
+            return false;
+        };
+
+        let Some(standby) = &mut self.standby else {
+            return false;
+        };
+
+        // The outgoing track keeps playing on the standby player
+        std::mem::swap(&mut self.player, standby);
+        standby.set_queued_uri(None);
+
+        let result = self.player.stop().and_then(|_| {
+            self.player.load(url.as_str());
+            self.player.set_replay_gain(gain);
+            self.player.set_fade(0.0);
+            self.player.play()
+        });
+        self.rate_applied = self.rate == 1.0;
+
+        self.crossfade = Some(Crossfade {
+            started: Instant::now(),
+            duration: remaining,
+        });
+
+        match up_next {
+            UpNext::Queued(track) => {
+                if let Some(idx) = self
+                    .state
+                    .queue
+                    .iter()
+                    .position(|t| t.entry_id == track.entry_id)
+                {
+                    self.state.queue.remove(idx);
+                }
+                self.state.queue_current = Some(track);
+            }
+            UpNext::Session(idx) => {
+                self.state.queue_current = None;
+                if let Some(session) = &mut self.state.session {
+                    session.index = idx;
+                }
+            }
+        }
+
+        self.pending_gapless_track_id = None;
+        self.pending_gapless_queue_track = None;
+        self.update_now_playing();
+        self.queue_next_uri();
+        self.state.progress = 0.0;
+        self.report(result);
+
+        true
+    }
+
+    /// Whether both players can ramp their volume for a crossfade
+    fn can_crossfade(&self) -> bool {
+        self.player.can_fade() && self.standby.as_ref().is_some_and(|s| s.can_fade())
+    }
+
+    /// The current player followed by the standby one
+    fn players(&self) -> impl Iterator<Item = &Player> {
+        std::iter::once(&self.player).chain(self.standby.as_ref())
+    }
+
+    /// Advance the volume ramps of a crossfade in progress
+    fn update_crossfade(&mut self) {
+        let Some(crossfade) = self.crossfade else {
+            return;
+        };
+
+        let Some(standby) = &self.standby else {
+            self.crossfade = None;
+            return;
+        };
+        standby.drain_bus();
+
+        let progress = if crossfade.duration.is_zero() {
+            1.0
+        } else {
+            crossfade.started.elapsed().as_secs_f64() / crossfade.duration.as_secs_f64()
+        };
+
+        if progress >= 1.0 {
+            self.finish_crossfade();
+            return;
+        }
+
+        // Equal power curves keep the combined loudness steady
+        self.player.set_fade((progress * FRAC_PI_2).sin());
+        standby.set_fade((progress * FRAC_PI_2).cos());
+    }
+
+    /// Move the applied equalizer gains one step towards the target
+    fn update_equalizer(&mut self) {
+        if self.equalizer_gains == self.equalizer_target {
+            return;
+        }
+
+        for (gain, target) in self.equalizer_gains.iter_mut().zip(self.equalizer_target) {
+            if (target - *gain).abs() <= EQUALIZER_RAMP_STEP {
+                *gain = target;
+            } else {
+                *gain += EQUALIZER_RAMP_STEP.copysign(target - *gain);
+            }
+        }
+
+        for player in self.players() {
+            player.set_equalizer(&self.equalizer_gains);
+        }
+    }
+
+    /// End a crossfade right away, stopping the outgoing track
+    fn finish_crossfade(&mut self) {
+        if self.crossfade.take().is_none() {
+            return;
+        }
+
+        if let Some(standby) = &mut self.standby {
+            if let Err(err) = standby.stop() {
+                eprintln!("Failed to stop crossfade: {}", err);
+            }
+            standby.drain_bus();
+            standby.set_fade(1.0);
+        }
+        self.player.set_fade(1.0);
+    }
+
+    /// Switch both players to the chosen output, or to the default one while
+    /// the chosen device isn't there
+    fn apply_output_device(&mut self) {
+        let id = self
+            .output_preferred
+            .clone()
+            .filter(|id| self.outputs.contains(id));
+
+        if id == self.output_active {
+            return;
+        }
+
+        for player in self.players() {
+            let sink = id.as_ref().and_then(|id| self.outputs.create_sink(id));
+            if let Err(err) = player.set_output(sink) {
+                eprintln!("Failed to change the output device: {}", err);
+            }
+        }
+
+        self.output_active = id;
+    }
+
+    /// Keep a failed player call to report it with the current track
+    fn report(&mut self, result: Result<(), PlayerError>) {
+        if let Err(err) = result {
+            let path = self.current_track().map(|t| t.path.clone());
+            self.errors.push((path, err));
+        }
+    }
+
+    /// Only the first error of a loaded track becomes an event, a file that
+    /// can't be loaded fails both the state change and on the bus
+    fn error_event(&mut self, path: Option<PathBuf>, err: PlayerError) -> Option<PlaybackEvent> {
+        if self.error_reported {
+            return None;
+        }
+        self.error_reported = true;
+
+        Some(PlaybackEvent::Error(path, err))
+    }
+
+    /// Linear volume that applies a track's ReplayGain under the current settings
+    ///
+    /// Falls back to the other mode's values when a track only has one pair of
+    /// tags, tracks without tags play unchanged.
+    fn replay_gain_volume(&self, track: &Track) -> f64 {
+        let metadata = &track.metadata;
+        let track_values = metadata.track_gain.map(|gain| (gain, metadata.track_peak));
+        let album_values = metadata.album_gain.map(|gain| (gain, metadata.album_peak));
+
+        let values = match self.replay_gain.mode {
+            ReplayGainMode::Off => None,
+            ReplayGainMode::Track => track_values.or(album_values),
+            ReplayGainMode::Album => album_values.or(track_values),
+        };
+
+        let Some((gain, peak)) = values else {
+            return 1.0;
+        };
+
+        let mut volume = 10f64.powf((gain + self.replay_gain.preamp as f64) / 20.0);
+
+        if self.replay_gain.prevent_clipping {
+            if let Some(peak) = peak.filter(|peak| *peak > 0.0) {
+                volume = volume.min(1.0 / peak);
+            }
+        }
+
+        // The volume element tops out at 10x
+        volume.clamp(0.0, 10.0)
+    }
+
     /// Called when STREAM_START confirms a gapless transition
     /// Advances the session index and queues the track after the new current one
     /// Identified by pending_gapless_track_id instead of recomputing the order
     /// The order may have changed since teh URI was queued
     fn advance_session_after_gapless(&mut self) {
-        if let Some(ref pending_id) = self.pending_gapless_track_id.clone() {
+        self.error_reported = false;
+        self.loop_start = None;
+        self.loop_end = None;
+        if let Some(track) = self.pending_gapless_queue_track.take() {
+            // GStreamer moved on to a queued track, it may have been queued
+            // again or repeated so only drop the entry that was handed over
+            if let Some(idx) = self
+                .state
+                .queue
+                .iter()
+                .position(|t| t.entry_id == track.entry_id)
+            {
+                self.state.queue.remove(idx);
+            }
+            self.state.queue_current = Some(track);
+        } else if let Some(ref pending_id) = self.pending_gapless_track_id.clone() {
+            self.state.queue_current = None;
+
             // Find the track GStreamer is now playing in the session
             if let Some(session) = &mut self.state.session {
                 if let Some(idx) = session
@@ -533,6 +1644,7 @@ impl PlaybackService {
                 }
             }
         } else {
+            self.state.queue_current = None;
             // Fallback: no ID reordered, advance by position
             if let Some(next_idx) = self.compute_next_index() {
                 if let Some(session) = &mut self.state.session {
@@ -549,3 +1661,24 @@ impl PlaybackService {
         self.state.progress = 0.0;
     }
 }
+
+/// Whether two tracks come from the same album, going by album and album artist
+fn same_album(a: &Track, b: &Track) -> bool {
+    let album_key = |track: &Track| {
+        let metadata = &track.metadata;
+        metadata.album.clone().map(|album| {
+            (
+                album,
+                metadata
+                    .album_artist
+                    .clone()
+                    .or_else(|| metadata.artist.clone()),
+            )
+        })
+    };
+
+    match (album_key(a), album_key(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
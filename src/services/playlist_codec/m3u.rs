@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: GPL-3.0
+// src/services/playlist_codec/m3u.rs
+
+use super::{PlaylistCodec, PlaylistDocument, PlaylistEntry};
+use anyhow::Result;
+use std::fmt::Write;
+
+/// Extended M3U, always written as UTF-8
+pub struct M3u;
+
+impl PlaylistCodec for M3u {
+    fn name(&self) -> &'static str {
+        "M3U"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["m3u8", "m3u"]
+    }
+
+    /// `#EXTINF`, `#EXTALB` and `#EXTART` lines are attached to the entry that
+    /// follows them, every other comment or directive is ignored.
+    fn decode(&self, content: &str) -> Result<PlaylistDocument> {
+        let mut document = PlaylistDocument::default();
+        let mut pending = PlaylistEntry::default();
+
+        for line in content.lines() {
+            let line = line.trim_start_matches('\u{feff}').trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(info) = line.strip_prefix("#EXTINF:") {
+                let (duration, title) = parse_extinf(info);
+                pending.duration = duration;
+                pending.title = title;
+                continue;
+            }
+
+            if let Some(album) = line.strip_prefix("#EXTALB:") {
+                pending.album = non_empty(album);
+                continue;
+            }
+
+            if let Some(artist) = line.strip_prefix("#EXTART:") {
+                pending.creator = non_empty(artist);
+                continue;
+            }
+
+            if let Some(title) = line.strip_prefix("#PLAYLIST:") {
+                document.title = non_empty(title);
+                continue;
+            }
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let mut entry = std::mem::take(&mut pending);
+            entry.location = line.to_string();
+            strip_creator(&mut entry);
+            document.entries.push(entry);
+        }
+
+        Ok(document)
+    }
+
+    fn encode(&self, document: &PlaylistDocument) -> String {
+        let mut content = String::from("#EXTM3U\n");
+
+        if let Some(title) = &document.title {
+            let _ = writeln!(content, "#PLAYLIST:{}", single_line(title));
+        }
+
+        for entry in &document.entries {
+            let duration = entry.duration.map(|d| d.round() as i64).unwrap_or(-1);
+
+            let display = match (&entry.creator, &entry.title) {
+                (Some(creator), Some(title)) => format!("{} - {}", creator, title),
+                (None, Some(title)) => title.clone(),
+                (Some(creator), None) => creator.clone(),
+                (None, None) => String::new(),
+            };
+
+            let _ = writeln!(content, "#EXTINF:{},{}", duration, single_line(&display));
+
+            if let Some(creator) = &entry.creator {
+                let _ = writeln!(content, "#EXTART:{}", single_line(creator));
+            }
+            if let Some(album) = &entry.album {
+                let _ = writeln!(content, "#EXTALB:{}", single_line(album));
+            }
+
+            let _ = writeln!(content, "{}", entry.location);
+        }
+
+        content
+    }
+}
+
+/// Split `#EXTINF:<duration>,<title>` into its parts
+///
+/// The title is kept whole, `Artist - Title` can't be split reliably without
+/// an `#EXTART` line, see `strip_creator`.
+fn parse_extinf(info: &str) -> (Option<f32>, Option<String>) {
+    let (duration, title) = match info.split_once(',') {
+        Some((duration, title)) => (duration, non_empty(title)),
+        None => (info, None),
+    };
+
+    // The duration may be followed by attributes, e.g. `123 tvg-id="..."`
+    let duration = duration
+        .split_whitespace()
+        .next()
+        .and_then(|d| d.parse::<f32>().ok())
+        .filter(|d| *d >= 0.0);
+
+    (duration, title)
+}
+
+/// Remove the `Artist - ` prefix written by `encode` when the artist is
+/// known from `#EXTART`, so a round trip doesn't repeat it
+fn strip_creator(entry: &mut PlaylistEntry) {
+    let (Some(creator), Some(title)) = (&entry.creator, &entry.title) else {
+        return;
+    };
+
+    if let Some(stripped) = title
+        .strip_prefix(creator.as_str())
+        .and_then(|rest| rest.strip_prefix(" - "))
+    {
+        entry.title = non_empty(stripped);
+    } else if title == creator {
+        entry.title = None;
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0
+// src/services/playlist_codec/mod.rs
+
+mod m3u;
+mod pls;
+mod xspf;
+
+use anyhow::Result;
+use m3u::M3u;
+use pls::Pls;
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+use xspf::Xspf;
+
+/// A playlist file as read from or written to disk
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistDocument {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// A single playlist file entry
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistEntry {
+    /// A file path, relative or absolute, or a URL
+    pub location: String,
+    pub title: Option<String>,
+    pub creator: Option<String>,
+    pub album: Option<String>,
+    /// Duration in seconds
+    pub duration: Option<f32>,
+}
+
+/// Reads and writes one playlist file format
+pub trait PlaylistCodec: Sync {
+    /// Format name shown in file dialogs
+    fn name(&self) -> &'static str;
+
+    /// File extensions handled by this codec, without the leading dot
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn decode(&self, content: &str) -> Result<PlaylistDocument>;
+
+    fn encode(&self, document: &PlaylistDocument) -> String;
+}
+
+/// Every supported format, the first one is the default for export
+pub static CODECS: &[&dyn PlaylistCodec] = &[&M3u, &Xspf, &Pls];
+
+/// Find the codec for a file by its extension
+pub fn for_path(path: &Path) -> Option<&'static dyn PlaylistCodec> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    CODECS
+        .iter()
+        .copied()
+        .find(|codec| codec.extensions().contains(&extension.as_str()))
+}
+
+/// Turn an entry location into an absolute path
+///
+/// Handles `file://` URLs as well as absolute and relative paths. Relative
+/// paths are resolved against `base_dir`. Returns `None` for remote URLs.
+pub fn resolve_location(location: &str, base_dir: &Path) -> Option<PathBuf> {
+    let path = if location.contains("://") {
+        let url = Url::parse(location).ok()?;
+        if url.scheme() != "file" {
+            return None;
+        }
+        url.to_file_path().ok()?
+    } else {
+        // Playlists written on Windows use backslashes
+        let location = if location.contains('\\') && !location.contains('/') {
+            location.replace('\\', "/")
+        } else {
+            location.to_string()
+        };
+        base_dir.join(location)
+    };
+
+    Some(normalize(&path))
+}
+
+/// Express `path` relative to `base`, or `None` if they share no root
+pub fn relative_path(base: &Path, path: &Path) -> Option<PathBuf> {
+    if !base.is_absolute() || !path.is_absolute() {
+        return None;
+    }
+
+    let base = normalize(base);
+    let target = normalize(path);
+    let base: Vec<Component> = base.components().collect();
+    let target: Vec<Component> = target.components().collect();
+
+    let common = base
+        .iter()
+        .zip(target.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    // Only the root is shared, an absolute path is clearer
+    if common <= 1 {
+        return None;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &target[common..] {
+        relative.push(component.as_os_str());
+    }
+
+    Some(relative)
+}
+
+/// Lexically remove `.` and `..` components without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+
+    normalized
+}
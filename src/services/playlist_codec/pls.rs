@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-3.0
+// src/services/playlist_codec/pls.rs
+
+use super::{PlaylistCodec, PlaylistDocument, PlaylistEntry};
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// PLS version 2
+///
+/// The format has no fields for artist or album, they are folded into the
+/// title as `Artist - Title` the same way most players do.
+pub struct Pls;
+
+impl PlaylistCodec for Pls {
+    fn name(&self) -> &'static str {
+        "PLS"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["pls"]
+    }
+
+    fn decode(&self, content: &str) -> Result<PlaylistDocument> {
+        let mut document = PlaylistDocument::default();
+        // Entries are numbered and may appear in any order
+        let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+        let mut has_header = false;
+
+        for line in content.lines() {
+            let line = line.trim_start_matches('\u{feff}').trim();
+
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                has_header |= line.eq_ignore_ascii_case("[playlist]");
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            if key == "x-title" || key == "title" {
+                document.title = (!value.is_empty()).then(|| value.to_string());
+                continue;
+            }
+
+            let (field, number) = match key.find(|c: char| c.is_ascii_digit()) {
+                Some(index) => key.split_at(index),
+                None => continue,
+            };
+            let Ok(number) = number.parse::<u32>() else {
+                continue;
+            };
+
+            let entry = entries.entry(number).or_default();
+            match field {
+                "file" => entry.location = value.to_string(),
+                "title" => entry.title = (!value.is_empty()).then(|| value.to_string()),
+                "length" => {
+                    entry.duration = value.parse::<f32>().ok().filter(|d| *d >= 0.0);
+                }
+                _ => {}
+            }
+        }
+
+        if !has_header {
+            return Err(anyhow!("Missing [playlist] section"));
+        }
+
+        document.entries = entries
+            .into_values()
+            .filter(|entry| !entry.location.is_empty())
+            .collect();
+
+        Ok(document)
+    }
+
+    fn encode(&self, document: &PlaylistDocument) -> String {
+        let mut content = String::from("[playlist]\n");
+
+        if let Some(title) = &document.title {
+            let _ = writeln!(content, "X-Title={}", single_line(title));
+        }
+
+        for (index, entry) in document.entries.iter().enumerate() {
+            let number = index + 1;
+
+            let _ = writeln!(content, "File{}={}", number, entry.location);
+
+            let title = match (&entry.creator, &entry.title) {
+                (Some(creator), Some(title)) => Some(format!("{} - {}", creator, title)),
+                (None, Some(title)) => Some(title.clone()),
+                (Some(creator), None) => Some(creator.clone()),
+                (None, None) => None,
+            };
+            if let Some(title) = title {
+                let _ = writeln!(content, "Title{}={}", number, single_line(&title));
+            }
+
+            let length = entry.duration.map(|d| d.round() as i64).unwrap_or(-1);
+            let _ = writeln!(content, "Length{}={}", number, length);
+        }
+
+        let _ = writeln!(content, "NumberOfEntries={}", document.entries.len());
+        content.push_str("Version=2\n");
+
+        content
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: GPL-3.0
+// src/services/playlist_codec/xspf.rs
+
+use super::{PlaylistCodec, PlaylistDocument, PlaylistEntry};
+use anyhow::{Result, anyhow};
+use std::fmt::Write;
+use std::path::Path;
+use url::Url;
+use urlencoding::{decode, encode};
+
+/// XML Shareable Playlist Format, version 1
+pub struct Xspf;
+
+impl PlaylistCodec for Xspf {
+    fn name(&self) -> &'static str {
+        "XSPF"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xspf"]
+    }
+
+    fn decode(&self, content: &str) -> Result<PlaylistDocument> {
+        let xml = roxmltree::Document::parse(content)?;
+        let root = xml.root_element();
+
+        if !root.has_tag_name("playlist") {
+            return Err(anyhow!("Not an XSPF playlist"));
+        }
+
+        let mut document = PlaylistDocument {
+            title: child_text(root, "title"),
+            entries: Vec::new(),
+        };
+
+        let tracks = root
+            .children()
+            .filter(|n| n.has_tag_name("trackList"))
+            .flat_map(|list| list.children())
+            .filter(|n| n.has_tag_name("track"));
+
+        for track in tracks {
+            // A track may list several locations, the first is the preferred one
+            let Some(location) = child_text(track, "location") else {
+                continue;
+            };
+
+            document.entries.push(PlaylistEntry {
+                location: location_from_uri(&location),
+                title: child_text(track, "title"),
+                creator: child_text(track, "creator"),
+                album: child_text(track, "album"),
+                duration: child_text(track, "duration")
+                    .and_then(|d| d.parse::<u64>().ok())
+                    .map(|ms| ms as f32 / 1000.0),
+            });
+        }
+
+        Ok(document)
+    }
+
+    fn encode(&self, document: &PlaylistDocument) -> String {
+        let mut content = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        content.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+
+        if let Some(title) = &document.title {
+            let _ = writeln!(content, "  <title>{}</title>", escape(title));
+        }
+
+        content.push_str("  <trackList>\n");
+
+        for entry in &document.entries {
+            content.push_str("    <track>\n");
+            let _ = writeln!(
+                content,
+                "      <location>{}</location>",
+                escape(&location_to_uri(&entry.location))
+            );
+            if let Some(title) = &entry.title {
+                let _ = writeln!(content, "      <title>{}</title>", escape(title));
+            }
+            if let Some(creator) = &entry.creator {
+                let _ = writeln!(content, "      <creator>{}</creator>", escape(creator));
+            }
+            if let Some(album) = &entry.album {
+                let _ = writeln!(content, "      <album>{}</album>", escape(album));
+            }
+            if let Some(duration) = entry.duration {
+                let _ = writeln!(
+                    content,
+                    "      <duration>{}</duration>",
+                    (duration * 1000.0).round() as u64
+                );
+            }
+            content.push_str("    </track>\n");
+        }
+
+        content.push_str("  </trackList>\n");
+        content.push_str("</playlist>\n");
+
+        content
+    }
+}
+
+/// Trimmed text of the first child element with the given name
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|n| n.has_tag_name(name))
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+/// Locations are URIs, absolute paths become `file://` URLs and relative
+/// paths are percent-encoded segment by segment
+fn location_to_uri(location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+
+    let path = Path::new(location);
+    if path.is_absolute() {
+        if let Ok(url) = Url::from_file_path(path) {
+            return url.to_string();
+        }
+    }
+
+    location
+        .split('/')
+        .map(|segment| encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Reverse of `location_to_uri`, `file://` URLs are left for
+/// `resolve_location` and relative references are decoded to a path
+fn location_from_uri(uri: &str) -> String {
+    if uri.contains("://") {
+        return uri.to_string();
+    }
+
+    decode(uri)
+        .map(|decoded| decoded.into_owned())
+        .unwrap_or_else(|_| uri.to_string())
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
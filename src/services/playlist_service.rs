@@ -1,11 +1,15 @@
 use crate::app::PlaylistId;
-use crate::constants::PLAYLISTS_DIR;
-//use crate::library::MediaMetaData;
+use crate::constants::{MISSING_TRACK_DURATION_TOLERANCE_SECS, PLAYLISTS_DIR};
+use crate::fl;
+use crate::library::{Library, MediaMetaData};
 use crate::playlist::{Playlist, Track};
+use crate::services::playlist_codec::{self, PlaylistDocument, PlaylistEntry};
+use crate::smart_playlist::SmartRules;
 use anyhow::{Result, anyhow};
-//use std::collections::HashMap;
+use sha256::digest;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-//use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use xdg::BaseDirectories;
 
@@ -70,6 +74,64 @@ impl PlaylistService {
         Ok(id)
     }
 
+    /// Create a new smart playlist and fill it from the library
+    pub fn create_smart(
+        &mut self,
+        name: String,
+        rules: SmartRules,
+        library: &Library,
+    ) -> Result<PlaylistId> {
+        if self.playlists.iter().any(|p| p.name() == name) {
+            return Err(anyhow!("Playlist '{}' already exists", name));
+        }
+
+        let mut playlist = Playlist::smart(name, rules);
+        playlist.refresh(library);
+        let id = playlist.id();
+
+        self.playlists.push(playlist);
+        self.save(id)?;
+
+        Ok(id)
+    }
+
+    /// Replace the name and rules of a smart playlist and refresh it
+    pub fn update_smart(
+        &mut self,
+        id: PlaylistId,
+        name: String,
+        rules: SmartRules,
+        library: &Library,
+    ) -> Result<()> {
+        if self
+            .playlists
+            .iter()
+            .any(|p| p.name() == name && p.id() != id)
+        {
+            return Err(anyhow!("Playlist '{}' already exists", name));
+        }
+
+        let playlist = self.get_mut(id)?;
+
+        if !playlist.is_smart() {
+            return Err(anyhow!("Playlist {} is not a smart playlist", id));
+        }
+
+        playlist.set_name(name);
+        playlist.set_smart_rules(rules);
+        playlist.refresh(library);
+        self.save(id)?;
+
+        Ok(())
+    }
+
+    /// Recompute every smart playlist from the library
+    pub fn refresh_smart(&mut self, library: &Library) {
+        for playlist in self.playlists.iter_mut().filter(|p| p.is_smart()) {
+            playlist.refresh(library);
+        }
+    }
+
     /// Rename playlist
     pub fn rename(&mut self, id: PlaylistId, new_name: String) -> Result<()> {
         let playlist = self.get_mut(id)?;
@@ -108,6 +170,10 @@ impl PlaylistService {
     pub fn add_tracks(&mut self, playlist_id: PlaylistId, tracks: Vec<Track>) -> Result<()> {
         let playlist = self.get_mut(playlist_id)?;
 
+        if playlist.is_smart() {
+            return Err(anyhow!("Cannot add tracks to a smart playlist"));
+        }
+
         for track in tracks {
             playlist.push(track);
         }
@@ -127,6 +193,10 @@ impl PlaylistService {
             return Err(anyhow!("Cannot remove tracks from library"));
         }
 
+        if playlist.is_smart() {
+            return Err(anyhow!("Cannot remove tracks from a smart playlist"));
+        }
+
         playlist.remove_selected();
 
         playlist.remove_selected();
@@ -135,6 +205,362 @@ impl PlaylistService {
         Ok(())
     }
 
+    /// Point user playlist entries at the current library
+    ///
+    /// Tracks are matched by path first and by content id second, so entries for
+    /// files that were moved or renamed follow them to their new location.
+    /// Returns the number of tracks that were updated.
+    pub fn relink_tracks(&mut self, library: &Library) -> Result<usize> {
+        let by_id: HashMap<&str, (&PathBuf, &MediaMetaData)> = library
+            .media
+            .iter()
+            .filter_map(|(path, metadata)| metadata.id.as_deref().map(|id| (id, (path, metadata))))
+            .collect();
+
+        let mut total = 0;
+        let mut changed_ids = Vec::new();
+
+        for playlist in self
+            .playlists
+            .iter_mut()
+            .filter(|p| !p.is_library() && !p.is_smart())
+        {
+            let mut changed = 0;
+
+            for track in playlist.tracks_mut() {
+                if let Some(metadata) = library.media.get(&track.path) {
+                    // Same path, the id scheme or the file contents changed
+                    if metadata.id != track.metadata.id {
+                        track.metadata = metadata.clone();
+                        changed += 1;
+                    }
+                } else if let Some((path, metadata)) =
+                    track.metadata.id.as_deref().and_then(|id| by_id.get(id))
+                {
+                    // File moved, follow it
+                    track.path = (*path).clone();
+                    track.metadata = (*metadata).clone();
+                    changed += 1;
+                }
+            }
+
+            if changed > 0 {
+                total += changed;
+                changed_ids.push(playlist.id());
+            }
+        }
+
+        for id in changed_ids {
+            self.save(id)?;
+        }
+
+        Ok(total)
+    }
+
+    /// Copy the library metadata of the given files into user playlist entries
+    /// Returns the number of entries updated
+    pub fn refresh_metadata(
+        &mut self,
+        library: &Library,
+        paths: &HashSet<PathBuf>,
+    ) -> Result<usize> {
+        let mut total = 0;
+        let mut changed_ids = Vec::new();
+
+        for playlist in self
+            .playlists
+            .iter_mut()
+            .filter(|p| !p.is_library() && !p.is_smart())
+        {
+            let mut changed = 0;
+
+            for track in playlist.tracks_mut() {
+                if !paths.contains(&track.path) {
+                    continue;
+                }
+
+                if let Some(metadata) = library.media.get(&track.path) {
+                    track.metadata = metadata.clone();
+                    changed += 1;
+                }
+            }
+
+            if changed > 0 {
+                total += changed;
+                changed_ids.push(playlist.id());
+            }
+        }
+
+        for id in changed_ids {
+            self.save(id)?;
+        }
+
+        Ok(total)
+    }
+
+    /// Flag user playlist entries whose file no longer exists or is no longer in
+    /// the library. Returns the number of missing entries.
+    pub fn validate(&mut self, library: &Library) -> usize {
+        let mut missing = 0;
+
+        for playlist in self
+            .playlists
+            .iter_mut()
+            .filter(|p| !p.is_library() && !p.is_smart())
+        {
+            for track in playlist.tracks_mut() {
+                track.missing = !library.media.contains_key(&track.path) || !track.path.exists();
+                if track.missing {
+                    missing += 1;
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// Try to re-match missing entries of a playlist against the library using
+    /// their tags and file name. Returns the number of resolved and unresolved entries.
+    pub fn locate_missing(
+        &mut self,
+        playlist_id: PlaylistId,
+        library: &Library,
+    ) -> Result<(usize, usize)> {
+        let playlist = self.get_mut(playlist_id)?;
+
+        if playlist.is_library() || playlist.is_smart() {
+            return Err(anyhow!("Cannot repair library or smart playlist"));
+        }
+
+        let mut resolved = 0;
+        let mut unresolved = 0;
+
+        for track in playlist.tracks_mut().iter_mut().filter(|t| t.missing) {
+            match Self::find_replacement(track, library) {
+                Some((path, metadata)) => {
+                    track.path = path.clone();
+                    track.metadata = metadata.clone();
+                    track.missing = false;
+                    resolved += 1;
+                }
+                None => unresolved += 1,
+            }
+        }
+
+        if resolved > 0 {
+            self.save(playlist_id)?;
+        }
+
+        Ok((resolved, unresolved))
+    }
+
+    /// Remove every missing entry from a playlist
+    pub fn remove_missing(&mut self, playlist_id: PlaylistId) -> Result<usize> {
+        let playlist = self.get_mut(playlist_id)?;
+
+        if playlist.is_library() {
+            return Err(anyhow!("Cannot remove tracks from library"));
+        }
+
+        if playlist.is_smart() {
+            return Err(anyhow!("Cannot remove tracks from a smart playlist"));
+        }
+
+        let removed = playlist.remove_missing();
+
+        if removed > 0 {
+            self.save(playlist_id)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Export a playlist, the format is picked from the file extension and
+    /// defaults to M3U8
+    ///
+    /// With `relative` set, paths are written relative to the folder the
+    /// playlist file is saved in.
+    pub fn export(&self, id: PlaylistId, path: &Path, relative: bool) -> Result<()> {
+        let playlist = self.get(id)?;
+        let codec = playlist_codec::for_path(path).unwrap_or(playlist_codec::CODECS[0]);
+
+        let base_dir = if relative { path.parent() } else { None };
+
+        let document = PlaylistDocument {
+            title: Some(playlist.name().to_string()),
+            entries: playlist
+                .tracks()
+                .iter()
+                .map(|track| Self::track_to_entry(track, base_dir))
+                .collect(),
+        };
+
+        fs::write(path, codec.encode(&document))?;
+
+        Ok(())
+    }
+
+    /// Import a playlist file as a new playlist
+    ///
+    /// Entries are resolved against the library. Entries that can't be resolved
+    /// are kept as missing tracks so exporting the playlist again loses nothing.
+    /// Returns the id of the new playlist and the unresolved entries.
+    pub fn import(
+        &mut self,
+        path: &Path,
+        library: &Library,
+    ) -> Result<(PlaylistId, Vec<PlaylistEntry>)> {
+        let codec = playlist_codec::for_path(path)
+            .ok_or_else(|| anyhow!("Unsupported playlist format: {:?}", path))?;
+
+        let bytes = fs::read(path)?;
+        let content = String::from_utf8_lossy(&bytes);
+        let document = codec.decode(&content)?;
+
+        let base_dir = path.parent().unwrap_or(Path::new("/"));
+
+        let mut tracks = Vec::new();
+        let mut unresolved = Vec::new();
+
+        for entry in document.entries {
+            let location = playlist_codec::resolve_location(&entry.location, base_dir);
+
+            let resolved = location.as_ref().and_then(|location| {
+                library.media.get_key_value(location).or_else(|| {
+                    // The playlist may reference the file through a symlink
+                    let canonical = fs::canonicalize(location).ok()?;
+                    library.media.get_key_value(&canonical)
+                })
+            });
+
+            let mut track = Track::new();
+
+            match resolved {
+                Some((track_path, metadata)) => {
+                    track.path = track_path.clone();
+                    track.metadata = metadata.clone();
+                }
+                None => {
+                    track.path = location.unwrap_or_else(|| PathBuf::from(&entry.location));
+                    track.metadata.id = Some(digest(entry.location.as_str()));
+                    track.metadata.title = entry.title.clone();
+                    track.metadata.artist = entry.creator.clone();
+                    track.metadata.album = entry.album.clone();
+                    track.metadata.duration = entry.duration;
+                    track.missing = true;
+                    unresolved.push(entry);
+                }
+            }
+
+            tracks.push(track);
+        }
+
+        let name = document
+            .title
+            .or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| fl!("imported-playlist"));
+
+        let id = self.create(self.unique_name(&name))?;
+        self.add_tracks(id, tracks)?;
+
+        Ok((id, unresolved))
+    }
+
+    /// Build a playlist file entry from a track
+    fn track_to_entry(track: &Track, base_dir: Option<&Path>) -> PlaylistEntry {
+        let location = base_dir
+            .and_then(|base| playlist_codec::relative_path(base, &track.path))
+            .unwrap_or_else(|| track.path.clone());
+
+        let title = track.metadata.title.clone().or_else(|| {
+            track
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+        });
+
+        PlaylistEntry {
+            location: location.to_string_lossy().into_owned(),
+            title,
+            creator: track.metadata.artist.clone(),
+            album: track.metadata.album.clone(),
+            duration: track.metadata.duration,
+        }
+    }
+
+    /// Append a number to `name` until it doesn't clash with an existing playlist
+    fn unique_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| self.playlists.iter().any(|p| p.name() == candidate);
+
+        if !taken(name) {
+            return name.to_string();
+        }
+
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| !taken(candidate))
+            .unwrap()
+    }
+
+    /// Find the library entry that best matches a missing track
+    ///
+    /// Title and artist count for two points, the file name for two, album and a
+    /// duration within tolerance for one each. A candidate needs at least four
+    /// points including either the title or the file name.
+    fn find_replacement<'a>(
+        track: &Track,
+        library: &'a Library,
+    ) -> Option<(&'a PathBuf, &'a MediaMetaData)> {
+        fn normalized(value: &Option<String>) -> Option<String> {
+            value
+                .as_deref()
+                .map(|v| v.trim().to_lowercase())
+                .filter(|v| !v.is_empty())
+        }
+
+        let title = normalized(&track.metadata.title);
+        let artist = normalized(&track.metadata.artist);
+        let album = normalized(&track.metadata.album);
+        let file_name = track.path.file_name();
+
+        library
+            .media
+            .iter()
+            .filter(|(path, _)| **path != track.path)
+            .filter_map(|(path, metadata)| {
+                let title_match = title.is_some() && title == normalized(&metadata.title);
+                let name_match = file_name.is_some() && file_name == path.file_name();
+
+                if !title_match && !name_match {
+                    return None;
+                }
+
+                let mut score = 0;
+                if title_match {
+                    score += 2;
+                }
+                if name_match {
+                    score += 2;
+                }
+                if artist.is_some() && artist == normalized(&metadata.artist) {
+                    score += 2;
+                }
+                if album.is_some() && album == normalized(&metadata.album) {
+                    score += 1;
+                }
+                if let (Some(a), Some(b)) = (track.metadata.duration, metadata.duration) {
+                    if (a - b).abs() <= MISSING_TRACK_DURATION_TOLERANCE_SECS {
+                        score += 1;
+                    }
+                }
+
+                (score >= 4).then_some((score, path, metadata))
+            })
+            // Highest score wins, ties go to the lowest path so the result is stable
+            .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(a.1)))
+            .map(|(_, path, metadata)| (path, metadata))
+    }
+
     /// Get playlist by ID
     pub fn get(&self, id: PlaylistId) -> Result<&Playlist> {
         self.playlists
@@ -177,6 +603,24 @@ impl PlaylistService {
         self.playlists.iter().filter(|p| !p.is_library())
     }
 
+    /// All playlists in navigation order, the library first and playlists
+    /// missing from `nav_order` last
+    pub fn ordered(&self, nav_order: &[PlaylistId]) -> Vec<&Playlist> {
+        let mut playlists: Vec<&Playlist> = self.get_library().into_iter().collect();
+        playlists.extend(
+            nav_order
+                .iter()
+                .filter_map(|id| self.get(*id).ok())
+                .filter(|p| !p.is_library()),
+        );
+        playlists.extend(
+            self.user_playlists()
+                .filter(|p| !nav_order.contains(&p.id())),
+        );
+
+        playlists
+    }
+
     /// Save playlist to disk
     pub fn save(&self, id: PlaylistId) -> Result<()> {
         let playlist = self.get(id)?;
@@ -189,7 +633,14 @@ impl PlaylistService {
         let mut file_path = self.xdg_dirs.create_data_directory("playlists")?;
         file_path.push(filename);
 
-        let content = serde_json::to_string_pretty(playlist)?;
+        // Smart playlist contents are computed, only the rules are stored
+        let content = if playlist.is_smart() {
+            let mut rules_only = playlist.clone();
+            rules_only.clear();
+            serde_json::to_string_pretty(&rules_only)?
+        } else {
+            serde_json::to_string_pretty(playlist)?
+        };
         fs::write(file_path, content)?;
 
         Ok(())
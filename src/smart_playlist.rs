@@ -0,0 +1,465 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use crate::app::{PlaylistId, SortDirection};
+use crate::fl;
+use crate::library::{Library, MediaMetaData};
+use crate::playlist::Track;
+use chrono::prelude::*;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Saved rules of a smart playlist
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SmartRules {
+    pub root: RuleGroup,
+    pub limit: Option<usize>,
+    pub sort_by: SmartSortBy,
+    pub sort_direction: SortDirection,
+}
+
+impl Default for SmartRules {
+    fn default() -> Self {
+        Self {
+            root: RuleGroup {
+                match_all: true,
+                rules: vec![RuleNode::Rule(Rule::default())],
+            },
+            limit: None,
+            sort_by: SmartSortBy::Artist,
+            sort_direction: SortDirection::Ascending,
+        }
+    }
+}
+
+impl SmartRules {
+    /// Build the tracks matching the rules from the library
+    ///
+    /// Entry ids are taken from `previous` for paths that were already in the
+    /// playlist so refreshing doesn't disturb a running playback session.
+    pub fn apply(&self, library: &Library, previous: &[Track]) -> Vec<Track> {
+        let now = Local::now().timestamp().max(0) as u64;
+
+        let mut matches: Vec<(&PathBuf, &MediaMetaData)> = library
+            .media
+            .iter()
+            .filter(|(path, metadata)| self.root.matches(path, metadata, now))
+            .collect();
+
+        self.sort(&mut matches);
+
+        if let Some(limit) = self.limit {
+            matches.truncate(limit);
+        }
+
+        let entry_ids: HashMap<&PathBuf, u32> = previous
+            .iter()
+            .map(|track| (&track.path, track.entry_id))
+            .collect();
+
+        matches
+            .into_iter()
+            .map(|(path, metadata)| {
+                let mut track = Track::new();
+                track.path = path.clone();
+                track.metadata = metadata.clone();
+
+                if let Some(entry_id) = entry_ids.get(path) {
+                    track.entry_id = *entry_id;
+                }
+
+                if let Some(added) =
+                    date_added(metadata).and_then(|secs| DateTime::from_timestamp(secs as i64, 0))
+                {
+                    track.date_added = added.with_timezone(&Local).to_string();
+                }
+
+                track
+            })
+            .collect()
+    }
+
+    fn sort(&self, matches: &mut [(&PathBuf, &MediaMetaData)]) {
+        if self.sort_by == SmartSortBy::Random {
+            matches.shuffle(&mut rand::rng());
+            return;
+        }
+
+        matches.sort_by(|(a_path, a), (b_path, b)| {
+            let ordering = match self.sort_by {
+                SmartSortBy::Artist => a
+                    .artist
+                    .cmp(&b.artist)
+                    .then(a.album.cmp(&b.album))
+                    .then(a.album_disc_number.cmp(&b.album_disc_number))
+                    .then(a.track_number.cmp(&b.track_number)),
+                SmartSortBy::Album => a
+                    .album
+                    .cmp(&b.album)
+                    .then(a.album_disc_number.cmp(&b.album_disc_number))
+                    .then(a.track_number.cmp(&b.track_number)),
+                SmartSortBy::Title => a.title.cmp(&b.title),
+                SmartSortBy::Duration => a
+                    .duration
+                    .partial_cmp(&b.duration)
+                    .unwrap_or(Ordering::Equal),
+                SmartSortBy::DateAdded => date_added(a).cmp(&date_added(b)),
+                SmartSortBy::Random => Ordering::Equal,
+            }
+            // Keep the order stable for equal keys
+            .then_with(|| a_path.cmp(b_path));
+
+            match self.sort_direction {
+                SortDirection::Ascending => ordering,
+                SortDirection::Descending => ordering.reverse(),
+            }
+        });
+    }
+
+    /// Get the group at `path`, the empty path is the root group
+    pub fn group_mut(&mut self, path: &[usize]) -> Option<&mut RuleGroup> {
+        let mut group = &mut self.root;
+
+        for index in path {
+            group = match group.rules.get_mut(*index)? {
+                RuleNode::Group(child) => child,
+                RuleNode::Rule(_) => return None,
+            };
+        }
+
+        Some(group)
+    }
+
+    /// Get the rule at `path`
+    pub fn rule_mut(&mut self, path: &[usize]) -> Option<&mut Rule> {
+        let (index, parent) = path.split_last()?;
+
+        match self.group_mut(parent)?.rules.get_mut(*index)? {
+            RuleNode::Rule(rule) => Some(rule),
+            RuleNode::Group(_) => None,
+        }
+    }
+
+    /// Remove the rule or group at `path`
+    pub fn remove(&mut self, path: &[usize]) {
+        let Some((index, parent)) = path.split_last() else {
+            return;
+        };
+
+        if let Some(group) = self.group_mut(parent) {
+            if *index < group.rules.len() {
+                group.rules.remove(*index);
+            }
+        }
+    }
+}
+
+/// A set of rules that must all (AND) or any (OR) match
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RuleGroup {
+    pub match_all: bool,
+    pub rules: Vec<RuleNode>,
+}
+
+impl RuleGroup {
+    fn matches(&self, path: &Path, metadata: &MediaMetaData, now: u64) -> bool {
+        // An empty group matches everything
+        if self.rules.is_empty() {
+            return true;
+        }
+
+        let mut results = self.rules.iter().map(|node| match node {
+            RuleNode::Rule(rule) => rule.matches(path, metadata, now),
+            RuleNode::Group(group) => group.matches(path, metadata, now),
+        });
+
+        if self.match_all {
+            results.all(|matched| matched)
+        } else {
+            results.any(|matched| matched)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RuleNode {
+    Rule(Rule),
+    Group(RuleGroup),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub field: RuleField,
+    pub operator: RuleOperator,
+    pub value: String,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            field: RuleField::Artist,
+            operator: RuleOperator::Contains,
+            value: String::new(),
+        }
+    }
+}
+
+impl Rule {
+    fn matches(&self, path: &Path, metadata: &MediaMetaData, now: u64) -> bool {
+        match self.field {
+            RuleField::Title => self.matches_text(metadata.title.as_deref()),
+            RuleField::Artist => self.matches_text(metadata.artist.as_deref()),
+            RuleField::Album => self.matches_text(metadata.album.as_deref()),
+            RuleField::AlbumArtist => self.matches_text(metadata.album_artist.as_deref()),
+            RuleField::Genre => self.matches_text(metadata.genre.as_deref()),
+            RuleField::Path => self.matches_text(path.to_str()),
+            RuleField::Duration => {
+                // Durations are entered in minutes, `m:ss` works as well
+                let value = parse_minutes(&self.value);
+                self.matches_number(metadata.duration.map(|d| d as f64), value)
+            }
+            RuleField::TrackNumber => {
+                let value = self.value.trim().parse::<f64>().ok();
+                self.matches_number(metadata.track_number.map(f64::from), value)
+            }
+            RuleField::DiscNumber => {
+                let value = self.value.trim().parse::<f64>().ok();
+                self.matches_number(metadata.album_disc_number.map(f64::from), value)
+            }
+            RuleField::DateAdded => {
+                let Some(added) = date_added(metadata) else {
+                    return false;
+                };
+                let Ok(days) = self.value.trim().parse::<u64>() else {
+                    return false;
+                };
+
+                // Compare in whole days ago
+                let age = now.saturating_sub(added) / SECONDS_PER_DAY;
+
+                match self.operator {
+                    RuleOperator::InLast => age < days,
+                    RuleOperator::NotInLast => age >= days,
+                    RuleOperator::Is => age == days,
+                    RuleOperator::IsNot => age != days,
+                    RuleOperator::GreaterThan => age > days,
+                    RuleOperator::LessThan => age < days,
+                    RuleOperator::Contains | RuleOperator::DoesNotContain => false,
+                }
+            }
+        }
+    }
+
+    fn matches_text(&self, field: Option<&str>) -> bool {
+        let field = field.unwrap_or_default().to_lowercase();
+        let value = self.value.trim().to_lowercase();
+
+        match self.operator {
+            RuleOperator::Is => field == value,
+            RuleOperator::IsNot => field != value,
+            RuleOperator::Contains => field.contains(&value),
+            RuleOperator::DoesNotContain => !field.contains(&value),
+            RuleOperator::GreaterThan => field > value,
+            RuleOperator::LessThan => field < value,
+            RuleOperator::InLast | RuleOperator::NotInLast => false,
+        }
+    }
+
+    fn matches_number(&self, field: Option<f64>, value: Option<f64>) -> bool {
+        let (Some(field), Some(value)) = (field, value) else {
+            return false;
+        };
+
+        match self.operator {
+            RuleOperator::Is => (field - value).abs() < 0.5,
+            RuleOperator::IsNot => (field - value).abs() >= 0.5,
+            RuleOperator::GreaterThan => field > value,
+            RuleOperator::LessThan => field < value,
+            RuleOperator::Contains
+            | RuleOperator::DoesNotContain
+            | RuleOperator::InLast
+            | RuleOperator::NotInLast => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RuleField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    Duration,
+    TrackNumber,
+    DiscNumber,
+    DateAdded,
+    Path,
+}
+
+impl RuleField {
+    pub const ALL: &'static [RuleField] = &[
+        RuleField::Title,
+        RuleField::Artist,
+        RuleField::Album,
+        RuleField::AlbumArtist,
+        RuleField::Genre,
+        RuleField::Duration,
+        RuleField::TrackNumber,
+        RuleField::DiscNumber,
+        RuleField::DateAdded,
+        RuleField::Path,
+    ];
+
+    pub fn label(&self) -> String {
+        match self {
+            RuleField::Title => fl!("title"),
+            RuleField::Artist => fl!("artist"),
+            RuleField::Album => fl!("album"),
+            RuleField::AlbumArtist => fl!("album-artist"),
+            RuleField::Genre => fl!("genre"),
+            RuleField::Duration => fl!("duration-minutes"),
+            RuleField::TrackNumber => fl!("track-number"),
+            RuleField::DiscNumber => fl!("album-disc-number"),
+            RuleField::DateAdded => fl!("date-added-days"),
+            RuleField::Path => fl!("file-path"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RuleOperator {
+    Is,
+    IsNot,
+    Contains,
+    DoesNotContain,
+    GreaterThan,
+    LessThan,
+    InLast,
+    NotInLast,
+}
+
+impl RuleOperator {
+    pub const ALL: &'static [RuleOperator] = &[
+        RuleOperator::Is,
+        RuleOperator::IsNot,
+        RuleOperator::Contains,
+        RuleOperator::DoesNotContain,
+        RuleOperator::GreaterThan,
+        RuleOperator::LessThan,
+        RuleOperator::InLast,
+        RuleOperator::NotInLast,
+    ];
+
+    pub fn label(&self) -> String {
+        match self {
+            RuleOperator::Is => fl!("rule-is"),
+            RuleOperator::IsNot => fl!("rule-is-not"),
+            RuleOperator::Contains => fl!("rule-contains"),
+            RuleOperator::DoesNotContain => fl!("rule-does-not-contain"),
+            RuleOperator::GreaterThan => fl!("rule-greater-than"),
+            RuleOperator::LessThan => fl!("rule-less-than"),
+            RuleOperator::InLast => fl!("rule-in-last"),
+            RuleOperator::NotInLast => fl!("rule-not-in-last"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SmartSortBy {
+    Artist,
+    Album,
+    Title,
+    Duration,
+    DateAdded,
+    Random,
+}
+
+impl SmartSortBy {
+    pub const ALL: &'static [SmartSortBy] = &[
+        SmartSortBy::Artist,
+        SmartSortBy::Album,
+        SmartSortBy::Title,
+        SmartSortBy::Duration,
+        SmartSortBy::DateAdded,
+        SmartSortBy::Random,
+    ];
+
+    pub fn label(&self) -> String {
+        match self {
+            SmartSortBy::Artist => fl!("artist"),
+            SmartSortBy::Album => fl!("album"),
+            SmartSortBy::Title => fl!("title"),
+            SmartSortBy::Duration => fl!("duration"),
+            SmartSortBy::DateAdded => fl!("date-added"),
+            SmartSortBy::Random => fl!("random"),
+        }
+    }
+}
+
+/// State of the smart playlist editor dialog
+#[derive(Clone, Debug)]
+pub struct SmartPlaylistEditor {
+    /// `None` while creating a new smart playlist
+    pub id: Option<PlaylistId>,
+    pub name: String,
+    pub rules: SmartRules,
+    /// Limit as typed, empty for no limit
+    pub limit: String,
+}
+
+impl SmartPlaylistEditor {
+    pub fn new() -> Self {
+        Self {
+            id: None,
+            name: String::new(),
+            rules: SmartRules::default(),
+            limit: String::new(),
+        }
+    }
+
+    pub fn edit(id: PlaylistId, name: &str, rules: &SmartRules) -> Self {
+        Self {
+            id: Some(id),
+            name: name.to_string(),
+            rules: rules.clone(),
+            limit: rules.limit.map(|l| l.to_string()).unwrap_or_default(),
+        }
+    }
+
+    /// The rules with the typed limit applied
+    pub fn finish(mut self) -> (Option<PlaylistId>, String, SmartRules) {
+        self.rules.limit = self.limit.trim().parse::<usize>().ok().filter(|l| *l > 0);
+        (self.id, self.name, self.rules)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.name.trim().is_empty()
+            && (self.limit.trim().is_empty() || self.limit.trim().parse::<usize>().is_ok())
+    }
+}
+
+/// Time the track was added to the library, falling back to the file time for
+/// entries scanned before it was tracked
+fn date_added(metadata: &MediaMetaData) -> Option<u64> {
+    metadata.date_added.or(metadata.file_modified)
+}
+
+/// Parse minutes as `5`, `5.5` or `5:30` into seconds
+fn parse_minutes(value: &str) -> Option<f64> {
+    let value = value.trim();
+
+    match value.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes = minutes.trim().parse::<f64>().ok()?;
+            let seconds = seconds.trim().parse::<f64>().ok()?;
+            Some(minutes * 60.0 + seconds)
+        }
+        None => value.parse::<f64>().ok().map(|minutes| minutes * 60.0),
+    }
+}
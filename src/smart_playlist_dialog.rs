@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: GPL-3.0
+
+use crate::app::{AppModel, DialogPage, Message, SortDirection};
+use crate::fl;
+use crate::smart_playlist::{
+    Rule, RuleField, RuleGroup, RuleNode, RuleOperator, SmartPlaylistEditor, SmartSortBy,
+};
+use cosmic::{
+    Element, cosmic_theme,
+    iced::{Alignment, Length},
+    theme, widget,
+};
+
+/// Editor for the name, rules, limit and sort order of a smart playlist
+pub fn smart_playlist_dialog<'a>(
+    app: &'a AppModel,
+    editor: &'a SmartPlaylistEditor,
+) -> widget::Dialog<'a, Message> {
+    let cosmic_theme::Spacing {
+        space_xxs,
+        space_xs,
+        space_s,
+        ..
+    } = theme::active().cosmic().spacing;
+
+    let rules = push_group(
+        app,
+        editor,
+        &editor.rules.root,
+        Vec::new(),
+        widget::column().spacing(space_xxs),
+    );
+
+    let is_new = editor.id.is_none();
+    let complete_maybe = editor.is_valid().then_some(Message::DialogComplete);
+
+    let sort_direction = match editor.rules.sort_direction {
+        SortDirection::Ascending => 0,
+        SortDirection::Descending => 1,
+    };
+
+    let options = widget::row()
+        .spacing(space_xs)
+        .align_y(Alignment::Center)
+        .push(widget::text(fl!("limit")))
+        .push(
+            widget::text_input(fl!("no-limit"), &editor.limit)
+                .on_input(edit(editor, |editor, limit: String| editor.limit = limit))
+                .width(Length::Fixed(80.0)),
+        )
+        .push(widget::text(fl!("sort-by")))
+        .push(widget::dropdown(
+            &app.smart_sort_labels,
+            SmartSortBy::ALL
+                .iter()
+                .position(|sort_by| *sort_by == editor.rules.sort_by),
+            edit(editor, |editor, index: usize| {
+                if let Some(sort_by) = SmartSortBy::ALL.get(index) {
+                    editor.rules.sort_by = sort_by.clone();
+                }
+            }),
+        ))
+        .push(widget::dropdown(
+            &app.sort_direction_labels,
+            Some(sort_direction),
+            edit(editor, |editor, index: usize| {
+                editor.rules.sort_direction = match index {
+                    1 => SortDirection::Descending,
+                    _ => SortDirection::Ascending,
+                };
+            }),
+        ));
+
+    widget::dialog()
+        .title(if is_new {
+            fl!("new-smart-playlist")
+        } else {
+            fl!("edit-smart-playlist")
+        })
+        .primary_action(
+            widget::button::suggested(if is_new { fl!("create") } else { fl!("save") })
+                .on_press_maybe(complete_maybe),
+        )
+        .secondary_action(widget::button::standard(fl!("cancel")).on_press(Message::DialogCancel))
+        .control(
+            widget::column()
+                .spacing(space_s)
+                .push(
+                    widget::text_input(fl!("playlist-name"), &editor.name)
+                        .on_input(edit(editor, |editor, name: String| editor.name = name)),
+                )
+                .push(widget::scrollable(rules).height(Length::Fixed(300.0)))
+                .push(options),
+        )
+}
+
+/// Add the header of a group followed by its rules and nested groups
+fn push_group<'a>(
+    app: &'a AppModel,
+    editor: &'a SmartPlaylistEditor,
+    group: &'a RuleGroup,
+    path: Vec<usize>,
+    column: widget::Column<'a, Message>,
+) -> widget::Column<'a, Message> {
+    let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+    let add_rule = {
+        let path = path.clone();
+        edited(editor, move |editor| {
+            if let Some(group) = editor.rules.group_mut(&path) {
+                group.rules.push(RuleNode::Rule(Rule::default()));
+            }
+        })
+    };
+
+    let add_group = {
+        let path = path.clone();
+        edited(editor, move |editor| {
+            if let Some(group) = editor.rules.group_mut(&path) {
+                group.rules.push(RuleNode::Group(RuleGroup {
+                    match_all: false,
+                    rules: vec![RuleNode::Rule(Rule::default())],
+                }));
+            }
+        })
+    };
+
+    let mut header = widget::row()
+        .spacing(space_xxs)
+        .align_y(Alignment::Center)
+        .push(indent(path.len()))
+        .push(widget::text(fl!("match")))
+        .push(widget::dropdown(
+            &app.smart_match_labels,
+            Some(if group.match_all { 0 } else { 1 }),
+            {
+                let path = path.clone();
+                edit(editor, move |editor, index: usize| {
+                    if let Some(group) = editor.rules.group_mut(&path) {
+                        group.match_all = index == 0;
+                    }
+                })
+            },
+        ))
+        .push(widget::horizontal_space())
+        .push(widget::button::text(fl!("add-rule")).on_press(add_rule))
+        .push(widget::button::text(fl!("add-group")).on_press(add_group));
+
+    // The root group can't be removed
+    if !path.is_empty() {
+        let path = path.clone();
+        header = header.push(
+            widget::button::icon(widget::icon::from_name("list-remove-symbolic"))
+                .on_press(edited(editor, move |editor| editor.rules.remove(&path))),
+        );
+    }
+
+    let mut column = column.push(header);
+
+    for (index, node) in group.rules.iter().enumerate() {
+        let mut child_path = path.clone();
+        child_path.push(index);
+
+        column = match node {
+            RuleNode::Rule(rule) => column.push(rule_row(app, editor, rule, child_path)),
+            RuleNode::Group(child) => push_group(app, editor, child, child_path, column),
+        };
+    }
+
+    column
+}
+
+/// A single rule as field, operator and value
+fn rule_row<'a>(
+    app: &'a AppModel,
+    editor: &'a SmartPlaylistEditor,
+    rule: &'a Rule,
+    path: Vec<usize>,
+) -> widget::Row<'a, Message> {
+    let cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
+
+    let on_field = {
+        let path = path.clone();
+        edit(editor, move |editor, index: usize| {
+            if let (Some(rule), Some(field)) =
+                (editor.rules.rule_mut(&path), RuleField::ALL.get(index))
+            {
+                rule.field = field.clone();
+            }
+        })
+    };
+
+    let on_operator = {
+        let path = path.clone();
+        edit(editor, move |editor, index: usize| {
+            if let (Some(rule), Some(operator)) =
+                (editor.rules.rule_mut(&path), RuleOperator::ALL.get(index))
+            {
+                rule.operator = operator.clone();
+            }
+        })
+    };
+
+    let on_value = {
+        let path = path.clone();
+        edit(editor, move |editor, value: String| {
+            if let Some(rule) = editor.rules.rule_mut(&path) {
+                rule.value = value;
+            }
+        })
+    };
+
+    let remove = {
+        let path = path.clone();
+        edited(editor, move |editor| editor.rules.remove(&path))
+    };
+
+    widget::row()
+        .spacing(space_xxs)
+        .align_y(Alignment::Center)
+        .push(indent(path.len()))
+        .push(widget::dropdown(
+            &app.smart_field_labels,
+            RuleField::ALL.iter().position(|field| *field == rule.field),
+            on_field,
+        ))
+        .push(widget::dropdown(
+            &app.smart_operator_labels,
+            RuleOperator::ALL
+                .iter()
+                .position(|operator| *operator == rule.operator),
+            on_operator,
+        ))
+        .push(
+            widget::text_input("", &rule.value)
+                .on_input(on_value)
+                .width(Length::Fill),
+        )
+        .push(
+            widget::button::icon(widget::icon::from_name("list-remove-symbolic")).on_press(remove),
+        )
+}
+
+/// Leading space for nested groups
+fn indent<'a>(depth: usize) -> Element<'a, Message> {
+    widget::horizontal_space()
+        .width(Length::Fixed(depth as f32 * 24.0))
+        .into()
+}
+
+/// Build a message handler that applies a change to a copy of the editor
+fn edit<T>(
+    editor: &SmartPlaylistEditor,
+    apply: impl Fn(&mut SmartPlaylistEditor, T) + Send + Sync + 'static,
+) -> impl Fn(T) -> Message + Send + Sync + 'static {
+    let editor = editor.clone();
+
+    move |value| {
+        let mut editor = editor.clone();
+        apply(&mut editor, value);
+        Message::UpdateDialog(DialogPage::SmartPlaylist(editor))
+    }
+}
+
+/// Apply a change to a copy of the editor right away, for buttons
+fn edited(editor: &SmartPlaylistEditor, apply: impl FnOnce(&mut SmartPlaylistEditor)) -> Message {
+    let mut editor = editor.clone();
+    apply(&mut editor);
+    Message::UpdateDialog(DialogPage::SmartPlaylist(editor))
+}